@@ -0,0 +1,136 @@
+use crate::cache;
+use crate::error::CmvmError;
+use crate::storage::Storage;
+use anyhow::{bail, Result};
+use std::collections::BTreeMap;
+use std::fs;
+
+/// Alias names must start with a letter so they never collide with version
+/// requests such as `3.28` or `~3.22`.
+pub fn is_valid_name(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_ascii_alphabetic())
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.')
+        && !["latest", "current"].contains(&name)
+}
+
+/// Points the alias `name` at the installed version `tag_name`. Aliases are
+/// symlinks under `<data_dir>/aliases`, so repointing one is a single `set`.
+pub fn set(name: &str, tag_name: &str, storage: &impl Storage) -> Result<()> {
+    if !is_valid_name(name) {
+        bail!(
+            "[cmvm] Invalid alias name `{}`. Use a name starting with a letter, e.g. `legacy` or `ci`.",
+            name
+        );
+    }
+
+    let Some((version_path, _)) = storage.find_version_dir(tag_name)? else {
+        bail!(CmvmError::NotInstalled(tag_name.to_string()));
+    };
+
+    let aliases_dir = storage.get_aliases_dir()?;
+    fs::create_dir_all(&aliases_dir)?;
+
+    let alias_path = aliases_dir.join(name);
+    if alias_path.symlink_metadata().is_ok() {
+        cache::delete(&alias_path)?;
+    }
+    std::os::unix::fs::symlink(version_path, alias_path)?;
+    Ok(())
+}
+
+pub fn remove(name: &str, storage: &impl Storage) -> Result<()> {
+    let alias_path = storage.get_aliases_dir()?.join(name);
+    if !is_valid_name(name) || alias_path.symlink_metadata().is_err() {
+        bail!("[cmvm] Alias {} not found.", name);
+    }
+    cache::delete(&alias_path)
+}
+
+/// Returns the version tag the alias points at.
+pub fn get(name: &str, storage: &impl Storage) -> Result<Option<String>> {
+    if !is_valid_name(name) {
+        return Ok(None);
+    }
+
+    let alias_path = storage.get_aliases_dir()?.join(name);
+    Ok(alias_path
+        .read_link()
+        .ok()
+        .and_then(|target| target.file_name().map(|n| n.to_string_lossy().to_string())))
+}
+
+/// Returns every alias with the version tag it points at, sorted by name.
+pub fn all(storage: &impl Storage) -> Result<BTreeMap<String, String>> {
+    let aliases_dir = storage.get_aliases_dir()?;
+    let mut aliases = BTreeMap::new();
+    if !aliases_dir.exists() {
+        return Ok(aliases);
+    }
+
+    for alias_path in cache::ls(&aliases_dir)? {
+        let Some(name) = alias_path
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+        else {
+            continue;
+        };
+        if let Some(tag_name) = get(&name, storage)? {
+            aliases.insert(name, tag_name);
+        }
+    }
+    Ok(aliases)
+}
+
+/// Returns the aliases pointing at `tag_name`.
+pub fn for_version(tag_name: &str, storage: &impl Storage) -> Result<Vec<String>> {
+    Ok(all(storage)?
+        .into_iter()
+        .filter(|(_, target)| target == tag_name)
+        .map(|(name, _)| name)
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::test_support::MockStorage;
+    use pretty_assertions::assert_eq;
+    use std::env;
+
+    #[test]
+    fn test_is_valid_name() {
+        assert!(is_valid_name("legacy"));
+        assert!(is_valid_name("ci-2024"));
+        assert!(!is_valid_name("3.28"));
+        assert!(!is_valid_name("~3.22"));
+        assert!(!is_valid_name("latest"));
+        assert!(!is_valid_name("../versions"));
+    }
+
+    #[test]
+    fn test_set_get_and_remove_alias() {
+        let cache_dir = env::temp_dir().join("cmvm_test_aliases_set");
+        let _ = fs::remove_dir_all(&cache_dir);
+        fs::create_dir_all(cache_dir.join("versions").join("3.20.0")).unwrap();
+        fs::create_dir_all(cache_dir.join("versions").join("3.28.1")).unwrap();
+        let storage = MockStorage::new(&cache_dir);
+
+        set("legacy", "3.20.0", &storage).unwrap();
+        set("ci", "3.20.0", &storage).unwrap();
+        set("ci", "3.28.1", &storage).unwrap();
+        let legacy = get("legacy", &storage).unwrap();
+        let for_3_28 = for_version("3.28.1", &storage).unwrap();
+        remove("legacy", &storage).unwrap();
+        let aliases = all(&storage).unwrap();
+        let missing = set("old", "2.8.0", &storage);
+        let _ = fs::remove_dir_all(&cache_dir);
+
+        assert_eq!(legacy, Some("3.20.0".to_string()));
+        assert_eq!(for_3_28, vec!["ci".to_string()]);
+        assert_eq!(aliases.len(), 1);
+        assert_eq!(aliases.get("ci"), Some(&"3.28.1".to_string()));
+        assert!(missing.is_err());
+    }
+}
@@ -1,13 +1,16 @@
 use crate::storage::Storage;
-use anyhow::Result;
+use anyhow::{Context, Result};
 use std::fs;
 use std::io::Read;
 use std::path::{Path, PathBuf};
 
+/// Creates `path` with any missing parents, as `--root` and the dir
+/// overrides may point anywhere, e.g. into a fresh CI cache directory.
 fn ensure_dir_exists(path: &Path) -> Result<()> {
     if !path.exists() {
-        fs::create_dir(path)?;
-        println!("[cmvm] Creating {}", path.display());
+        fs::create_dir_all(path)
+            .with_context(|| format!("[cmvm] Failed to create {}", path.display()))?;
+        eprintln!("[cmvm] Creating {}", path.display());
     }
     Ok(())
 }
@@ -60,6 +63,7 @@ pub fn ls(path: &Path) -> Result<Vec<PathBuf>> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::storage::test_support::MockStorage;
     use std::env;
     use std::io::Write;
 
@@ -73,6 +77,25 @@ mod tests {
         let _ = fs::remove_dir_all(&base);
     }
 
+    #[test]
+    fn test_bootstrap_creates_missing_parents() {
+        let base = env::temp_dir().join("cmvm_test_cache_bootstrap");
+        let _ = fs::remove_dir_all(&base);
+        let storage = MockStorage {
+            root: base.join("a/b"),
+            cache_dir: base.join("ci/cache/cmvm"),
+            session_link: None,
+        };
+
+        bootstrap(&storage).unwrap();
+        let versions_created = base.join("a/b/versions").is_dir();
+        let cache_created = base.join("ci/cache/cmvm").is_dir();
+        let _ = fs::remove_dir_all(&base);
+
+        assert!(versions_created);
+        assert!(cache_created);
+    }
+
     #[test]
     fn test_open_file_reads_contents() {
         let base = env::temp_dir().join("cmvm_test_cache_open_file");
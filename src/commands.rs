@@ -1,111 +1,689 @@
-use crate::storage::Storage;
-use crate::{package, platform::is_supported_platform, releases, versions::Version};
+use crate::completions::{self, CompletionShell};
+use crate::config::{self, Config};
+use crate::constants::{SYSTEM_CONFIG_FILE, VERSION_ENV_VAR, VERSION_FILE_NAME};
+use crate::error::CmvmError;
+use crate::manifest::{self, Manifest};
+use crate::output::{
+    self, CurrentReport, DetectReport, DoctorReport, InstallReport, OutputFormat, PruneReport,
+    PrunedVersion, RemoteVersion, UninstallReport, UseReport, VersionInfo,
+};
+use crate::project::{self, VersionSource};
+use crate::prune::{self, PrunePolicy};
+use crate::shell::{self, Shell};
+use crate::source::{self, BuildOptions, Source};
+use crate::storage::{self, Storage, Store};
+use crate::{
+    aliases, detect, doctor, multishell, package,
+    platform::is_supported_platform,
+    releases, resolver, shims, upgrade,
+    versions::{RemoteFilter, Version},
+};
 use anyhow::{bail, Result};
+use std::env;
+use std::ffi::OsString;
+use std::io;
+use std::os::unix::process::CommandExt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
 
-pub fn install_version(v: &str, storage: &impl Storage) -> Result<()> {
-    releases::build_cache(storage)?;
-
-    let versions_dir = storage.get_versions_dir()?;
+/// Returns the version given on the command line or, when omitted, the one
+/// requested by `CMVM_VERSION` or the closest `.cmake-version` file.
+pub fn get_version_argument(v: Option<String>) -> Result<String> {
+    if let Some(v) = v {
+        return Ok(v);
+    }
 
-    if let Some(version) = releases::get_release(v.trim(), storage)? {
-        if versions_dir.join(version.get_tag_name()).exists() {
-            println!("[cmvm] Version {} already installed.", v);
-            use_version(v, storage)?;
-            return Ok(());
+    match project::get_requested_version(&env::current_dir()?)? {
+        Some((version, source)) => {
+            eprintln!("[cmvm] Using version {} from {}.", version, source);
+            Ok(version)
         }
+        None => bail!(
+            "[cmvm] No version given, {} is not set and no {} file found.",
+            VERSION_ENV_VAR,
+            VERSION_FILE_NAME
+        ),
+    }
+}
+
+pub fn install_version(
+    v: &str,
+    pre: bool,
+    format: OutputFormat,
+    config: &Config,
+    storage: &impl Storage,
+) -> Result<()> {
+    releases::build_cache(config, storage)?;
+
+    let Some(version) = resolver::resolve_remote_with(v, pre, storage)? else {
+        bail!(releases::version_not_found(v, config));
+    };
+    install_release(version, format, config, storage)
+}
 
+pub fn install_from_project(
+    format: OutputFormat,
+    config: &Config,
+    storage: &impl Storage,
+) -> Result<()> {
+    releases::build_cache(config, storage)?;
+
+    let detection = detect::detect(&env::current_dir()?, storage)?;
+    eprintln!("[cmvm] Detected {}.", detection.reason);
+    install_release(detection.version, format, config, storage)
+}
+
+/// Installs `version`, taken from the release index, unless it is already
+/// installed, and makes it the active version.
+fn install_release(
+    mut version: Version,
+    format: OutputFormat,
+    config: &Config,
+    storage: &impl Storage,
+) -> Result<()> {
+    let tag_name = version.get_tag_name();
+    let already_installed = storage.find_version_dir(&tag_name)?.is_some();
+
+    if !already_installed {
         if !is_supported_platform() {
-            bail!("Platform not supported.");
+            bail!(CmvmError::UnsupportedPlatform);
         }
 
-        match package::get_cmake_release(&version, storage) {
-            Ok(()) => {
-                println!(
-                    "[cmvm] Version {} installed successfully.",
-                    version.get_tag_name()
+        package::get_cmake_release(&version, config, storage)?;
+        refresh_shims(storage)?;
+    }
+
+    version.r#use(storage)?;
+
+    if format.is_json() {
+        let Some((version_path, _)) = storage.find_version_dir(&tag_name)? else {
+            bail!(CmvmError::NotInstalled(tag_name));
+        };
+        return output::print_json(&InstallReport {
+            path: version_path,
+            version: tag_name,
+            already_installed,
+            active: true,
+        });
+    }
+
+    match already_installed {
+        true => println!("[cmvm] Version {} already installed.", tag_name),
+        false => println!("[cmvm] Version {} installed successfully.", tag_name),
+    }
+    print_use_message(&tag_name);
+    if !already_installed {
+        println!("[cmvm] Done.");
+    }
+    Ok(())
+}
+
+pub fn install_from_file(
+    archive: &Path,
+    name: Option<&str>,
+    format: OutputFormat,
+    storage: &impl Storage,
+) -> Result<()> {
+    let tag_name = package::install_archive(archive, name, storage)?;
+    refresh_shims(storage)?;
+    Version::from_tag_name(&tag_name).r#use(storage)?;
+
+    if format.is_json() {
+        return output::print_json(&InstallReport {
+            path: storage.get_versions_dir()?.join(&tag_name),
+            version: tag_name,
+            already_installed: false,
+            active: true,
+        });
+    }
+
+    println!(
+        "[cmvm] Version {} installed successfully from {}.",
+        tag_name,
+        archive.display()
+    );
+    print_use_message(&tag_name);
+    println!("[cmvm] Done.");
+    Ok(())
+}
+
+pub fn install_from_source(
+    source: &str,
+    name: Option<&str>,
+    options: &BuildOptions,
+    format: OutputFormat,
+    config: &Config,
+    storage: &impl Storage,
+) -> Result<()> {
+    // only versions and git refs need the release index
+    if !Path::new(source).exists() {
+        releases::build_cache(config, storage)?;
+    }
+
+    let source = Source::parse(source, storage)?;
+    let tag_name = source::install(&source, name, options, config, storage)?;
+    refresh_shims(storage)?;
+    Version::from_tag_name(&tag_name).r#use(storage)?;
+
+    if format.is_json() {
+        return output::print_json(&InstallReport {
+            path: storage.get_versions_dir()?.join(&tag_name),
+            version: tag_name,
+            already_installed: false,
+            active: true,
+        });
+    }
+
+    println!(
+        "[cmvm] Version {} built and installed successfully.",
+        tag_name
+    );
+    print_use_message(&tag_name);
+    println!("[cmvm] Done.");
+    Ok(())
+}
+
+pub fn detect_version(format: OutputFormat, config: &Config, storage: &impl Storage) -> Result<()> {
+    releases::build_cache(config, storage)?;
+
+    let detection = detect::detect(&env::current_dir()?, storage)?;
+
+    if format.is_json() {
+        return output::print_json(&DetectReport {
+            version: detection.version.get_tag_name(),
+            installed: detection.installed,
+            reason: detection.reason,
+        });
+    }
+
+    println!(
+        "[cmvm] {}: {}.",
+        detection.version.get_tag_name(),
+        detection.reason
+    );
+    if !detection.installed {
+        println!(
+            "[cmvm] Version {} is not installed. Use `cmvm install --from-project` to install it.",
+            detection.version.get_tag_name()
+        );
+    }
+    Ok(())
+}
+
+pub fn uninstall_version(v: &str, format: OutputFormat, storage: &impl Storage) -> Result<()> {
+    let Some(version) = resolver::resolve_installed(v, storage)? else {
+        bail!(CmvmError::NotInstalled(v.to_string()));
+    };
+    let v = version.get_tag_name();
+
+    releases::delete_cache_release(&v, storage)?;
+
+    let removed_aliases = aliases::for_version(&v, storage)?;
+    for alias in &removed_aliases {
+        aliases::remove(alias, storage)?;
+    }
+
+    if format.is_json() {
+        return output::print_json(&UninstallReport {
+            version: v,
+            removed_aliases,
+        });
+    }
+
+    for alias in &removed_aliases {
+        println!("[cmvm] Alias {} removed.", alias);
+    }
+    println!("[cmvm] Version {} uninstalled successfully.", v);
+    Ok(())
+}
+
+pub fn show_version_info(
+    v: &str,
+    format: OutputFormat,
+    config: &Config,
+    storage: &impl Storage,
+) -> Result<()> {
+    releases::build_cache(config, storage)?;
+
+    let Some(version) = resolver::resolve_remote(v, storage)? else {
+        bail!(releases::version_not_found(v, config));
+    };
+    let tag_name = version.get_tag_name();
+    let version_path = storage.find_version_dir(&tag_name)?.map(|(path, _)| path);
+    let installed = version_path.is_some();
+
+    let info = VersionInfo {
+        prerelease: version.is_rc(),
+        published_at: version.published_at.clone(),
+        html_url: version.html_url.clone(),
+        asset: package::filter_platform_assets(&version)
+            .first()
+            .cloned()
+            .cloned(),
+        path: version_path,
+        installed,
+        aliases: aliases::for_version(&tag_name, storage)?,
+        notes: version.body.clone(),
+        version: tag_name,
+    };
+
+    if format.is_json() {
+        return output::print_json(&info);
+    }
+
+    println!("{}", output::format_version_info(&info));
+    Ok(())
+}
+
+pub fn list_outdated(format: OutputFormat, config: &Config, storage: &impl Storage) -> Result<()> {
+    releases::build_cache(config, storage)?;
+
+    let outdated = upgrade::outdated(&Version::list_remote(storage)?, storage)?;
+
+    if format.is_json() {
+        return output::print_json(&outdated);
+    }
+
+    match outdated.is_empty() {
+        true => println!("[cmvm] All installed versions are up to date."),
+        false => println!("{}", output::format_outdated(&outdated)),
+    }
+    Ok(())
+}
+
+pub fn upgrade_versions(
+    v: Option<&str>,
+    minor: bool,
+    remove_old: bool,
+    format: OutputFormat,
+    config: &Config,
+    storage: &impl Storage,
+) -> Result<()> {
+    releases::build_cache(config, storage)?;
+
+    let remote = Version::list_remote(storage)?;
+    let installed = match v {
+        Some(v) => match resolver::resolve_installed(v, storage)? {
+            Some(version) => vec![version],
+            None => bail!(CmvmError::NotInstalled(v.to_string())),
+        },
+        // the system store is upgraded by whoever administers it
+        None => Version::all_installed_in(Store::User, storage)?,
+    };
+    if remove_old {
+        for version in &installed {
+            let tag_name = version.get_tag_name();
+            if let Some((path, Store::System)) = storage.find_version_dir(&tag_name)? {
+                bail!(
+                    "[cmvm] Version {} is installed in the system store at {}, so `--remove-old` cannot remove it.",
+                    tag_name,
+                    path.display()
                 );
-                use_version(v, storage)?;
-                println!("[cmvm] Done.");
             }
-            Err(e) => println!(
-                "[cmvm] Error while installing version {}: {}",
-                version.get_tag_name(),
-                e
-            ),
         }
-    } else {
-        println!("[cmvm] Version {} not found.", v);
     }
 
+    let mut upgrades = Vec::new();
+    for version in installed {
+        let old = version.get_tag_name();
+        let Some(new) = upgrade::find_upgrade(&version, &remote, minor) else {
+            if v.is_some() && !format.is_json() {
+                println!("[cmvm] Version {} is up to date.", old);
+            }
+            continue;
+        };
+        if !format.is_json() {
+            println!("[cmvm] Upgrading {} to {}.", old, new.get_tag_name());
+        }
+        let upgrade = upgrade::apply(&old, &new, remove_old, config, storage)?;
+        if !format.is_json() {
+            println!("{}", output::format_upgrade(&upgrade));
+        }
+        upgrades.push(upgrade);
+    }
+
+    if !upgrades.is_empty() {
+        refresh_shims(storage)?;
+    }
+
+    if format.is_json() {
+        return output::print_json(&upgrades);
+    }
+    if upgrades.is_empty() && v.is_none() {
+        println!("[cmvm] All installed versions are up to date.");
+    }
     Ok(())
 }
 
-pub fn uninstall_version(v: &str, storage: &impl Storage) -> Result<()> {
-    match releases::delete_cache_release(v, storage) {
-        Ok(()) => println!("[cmvm] Version {} uninstalled successfully.", v),
-        Err(e) => println!("[cmvm] Version {} is not installed. {}", v, e),
+pub fn prune_versions(
+    policy: &PrunePolicy,
+    dry_run: bool,
+    format: OutputFormat,
+    storage: &impl Storage,
+) -> Result<()> {
+    let candidates = prune::plan(policy, storage)?;
+    if candidates.is_empty() && !format.is_json() {
+        println!("[cmvm] Nothing to prune.");
+        return Ok(());
+    }
+
+    let mut removed = Vec::new();
+    for candidate in candidates {
+        let tag_name = candidate.version.get_tag_name();
+        let size = prune::format_size(candidate.size);
+        if !dry_run {
+            releases::delete_cache_release(&tag_name, storage)?;
+        }
+        if !format.is_json() {
+            match dry_run {
+                true => println!("[cmvm] Would remove {} ({}).", tag_name, size),
+                false => println!("[cmvm] Removed {} ({}).", tag_name, size),
+            }
+        }
+        removed.push(PrunedVersion {
+            version: tag_name,
+            size: candidate.size,
+        });
+    }
+    let reclaimed = removed.iter().map(|v| v.size).sum();
+
+    if format.is_json() {
+        return output::print_json(&PruneReport {
+            dry_run,
+            removed,
+            reclaimed,
+        });
+    }
+
+    match dry_run {
+        true => println!(
+            "[cmvm] {} would be reclaimed. Run without `--dry-run` to remove.",
+            prune::format_size(reclaimed)
+        ),
+        false => println!("[cmvm] {} reclaimed.", prune::format_size(reclaimed)),
     }
     Ok(())
 }
 
-pub fn list_remote_versions(storage: &impl Storage) -> Result<()> {
-    releases::build_cache(storage)?;
+pub fn list_remote_versions(
+    filter: &RemoteFilter,
+    format: OutputFormat,
+    config: &Config,
+    storage: &impl Storage,
+) -> Result<()> {
+    releases::build_cache(config, storage)?;
+
+    let versions = Version::list_remote_filtered(filter, storage)?;
+    let remote_versions = versions
+        .iter()
+        .map(|version| -> Result<RemoteVersion> {
+            Ok(RemoteVersion {
+                version: version.get_tag_name(),
+                installed: storage.find_version_dir(&version.get_tag_name())?.is_some(),
+                assets: package::filter_platform_assets(version)
+                    .into_iter()
+                    .cloned()
+                    .collect(),
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    if format.is_json() {
+        return output::print_json(&remote_versions);
+    }
+
+    if remote_versions.is_empty() {
+        println!("[cmvm] No versions match the given filters.");
+        return Ok(());
+    }
 
     println!("[cmvm] List of available versions to install:");
-    match Version::list_remote(storage) {
-        Ok(versions) => println!("{}", versions),
-        Err(_) => println!("[cmvm] There is no versions installed yet."),
+    println!("{}", output::format_remote_versions(&remote_versions));
+    if remote_versions.iter().any(|v| v.installed) {
+        println!("[cmvm] * installed");
+    }
+    Ok(())
+}
+
+pub fn list_versions(format: OutputFormat, storage: &impl Storage) -> Result<()> {
+    let versions = Version::list(storage)?;
+
+    if format.is_json() {
+        return output::print_json(&versions);
+    }
+
+    if !versions.is_empty() {
+        println!("[cmvm] Installed versions:");
+        println!("{}", output::format_installed_versions(&versions));
+    } else {
+        println!("[cmvm] No cmake versions installed yet. Use `cmvm install <version>` to install your first cmake version.");
+        println!("[cmvm] Type `cmvm help` for more information.");
+    }
+    Ok(())
+}
+
+pub fn use_version(v: &str, format: OutputFormat, storage: &impl Storage) -> Result<()> {
+    let Some(mut version) = resolver::resolve_installed(v, storage)? else {
+        bail!(CmvmError::NotInstalled(v.to_string()));
     };
+
+    version.r#use(storage)?;
+
+    if format.is_json() {
+        let tag_name = version.get_tag_name();
+        let Some((path, _)) = storage.find_version_dir(&tag_name)? else {
+            bail!(CmvmError::NotInstalled(tag_name));
+        };
+        return output::print_json(&UseReport {
+            version: tag_name,
+            path,
+            session: multishell::is_active(),
+        });
+    }
+
+    print_use_message(&version.get_tag_name());
     Ok(())
 }
 
-pub fn list_versions(storage: &impl Storage) -> Result<()> {
-    match Version::list(storage) {
-        Ok(versions) => {
-            if !versions.is_empty() {
-                println!("[cmvm] Installed versions:");
-                println!("{}", versions);
-            } else {
-                println!("[cmvm] No cmake versions installed yet. Use `cmvm install <version>` to install your first cmake version.");
-                println!("[cmvm] Type `cmvm help` for more information.");
+fn print_use_message(tag_name: &str) {
+    match multishell::is_active() {
+        true => println!("[cmvm] Version {} set for this shell session.", tag_name),
+        false => println!("[cmvm] Version {} set as default.", tag_name),
+    }
+}
+
+/// Runs `command` with `v` first on PATH. The current process is replaced by
+/// the command, so stdio, signals and the exit code are the command's own.
+pub fn exec_version(
+    v: &str,
+    install: bool,
+    command: &[String],
+    config: &Config,
+    storage: &impl Storage,
+) -> Result<()> {
+    let version = match resolver::resolve_installed(v, storage)? {
+        Some(version) => version,
+        None if install => {
+            releases::build_cache(config, storage)?;
+            let Some(version) = resolver::resolve_remote(v, storage)? else {
+                bail!(releases::version_not_found(v, config));
+            };
+            if storage.find_version_dir(&version.get_tag_name())?.is_none() {
+                if !is_supported_platform() {
+                    bail!(CmvmError::UnsupportedPlatform);
+                }
+                package::get_cmake_release(&version, config, storage)?;
+                refresh_shims(storage)?;
             }
+            version
         }
-        Err(_) => println!("[cmvm] There is no versions installed yet."),
+        None => {
+            eprintln!("[cmvm] Pass `--install` to install it on the fly.");
+            bail!(CmvmError::NotInstalled(v.to_string()))
+        }
+    };
+
+    let Some((program, args)) = command.split_first() else {
+        bail!("[cmvm] No command given.");
+    };
+    let Some((version_dir, _)) = storage.find_version_dir(&version.get_tag_name())? else {
+        bail!(CmvmError::NotInstalled(version.get_tag_name()));
+    };
+    let bin_dir = version_dir.join("bin");
+
+    let error = Command::new(program)
+        .args(args)
+        .env("PATH", shell::prepend_to_path(&bin_dir)?)
+        .env(VERSION_ENV_VAR, version.get_tag_name())
+        .exec();
+
+    bail!("[cmvm] Failed to run `{}`: {}", program, error)
+}
+
+pub fn set_alias(name: &str, v: &str, storage: &impl Storage) -> Result<()> {
+    let Some(version) = resolver::resolve_installed(v, storage)? else {
+        bail!(CmvmError::NotInstalled(v.to_string()));
     };
+
+    aliases::set(name, &version.get_tag_name(), storage)?;
+    println!("[cmvm] Alias {} -> {}.", name, version.get_tag_name());
     Ok(())
 }
 
-pub fn use_version(v: &str, storage: &impl Storage) -> Result<()> {
-    if let Some(mut version) = releases::get_release(v.trim(), storage)? {
-        match version.r#use(storage) {
-            Ok(_) => println!("[cmvm] Version {} set as default.", version.get_tag_name()),
-            Err(e) => println!(
-                "[cmvm] Error when trying to set version {}: {}",
-                version.get_tag_name(),
-                e
-            ),
-        }
-    } else {
-        println!("[cmvm] Version {} not found.", v);
+pub fn remove_alias(name: &str, storage: &impl Storage) -> Result<()> {
+    aliases::remove(name, storage)?;
+    println!("[cmvm] Alias {} removed.", name);
+    Ok(())
+}
+
+pub fn list_aliases(format: OutputFormat, storage: &impl Storage) -> Result<()> {
+    let aliases = aliases::all(storage)?;
+
+    if format.is_json() {
+        return output::print_json(&aliases);
+    }
+
+    if aliases.is_empty() {
+        println!("[cmvm] No aliases yet. Use `cmvm alias <name> <version>` to create one.");
+    }
+    for (name, tag_name) in aliases {
+        println!("[cmvm] {} -> {}", name, tag_name);
     }
     Ok(())
 }
 
-pub fn current_version(storage: &impl Storage) -> Result<()> {
-    let current_version_dir = storage.get_current_version_dir()?;
-    match current_version_dir.read_link() {
-        Ok(path) => {
-            if let Some(name) = path.file_name() {
-                println!("[cmvm] {}", name.to_string_lossy());
-            }
+pub fn get_setting(key: &str, config: &Config) -> Result<()> {
+    match config.get(key)? {
+        Some(value) => println!("{}", value),
+        None => bail!("[cmvm] {} is not set.", key),
+    }
+    Ok(())
+}
+
+pub fn set_setting(key: &str, value: &str, system: bool) -> Result<()> {
+    let path = config_file(system)?;
+    config::set_in_file(&path, key, value)?;
+    println!("[cmvm] {} = {} written to {}.", key, value, path.display());
+    Ok(())
+}
+
+pub fn unset_setting(key: &str, system: bool) -> Result<()> {
+    let path = config_file(system)?;
+    config::unset_in_file(&path, key)?;
+    println!("[cmvm] {} removed from {}.", key, path.display());
+    Ok(())
+}
+
+fn config_file(system: bool) -> Result<PathBuf> {
+    match system {
+        true => Ok(PathBuf::from(SYSTEM_CONFIG_FILE)),
+        false => storage::user_config_file(),
+    }
+}
+
+pub fn list_settings(config: &Config, format: OutputFormat) -> Result<()> {
+    if format.is_json() {
+        return output::print_json(config);
+    }
+
+    let mut empty = true;
+    for (key, _) in config::SETTINGS {
+        if let Some(value) = config.get(key)? {
+            println!("[cmvm] {} = {}", key, value);
+            empty = false;
         }
-        Err(_) => println!(
+    }
+    if empty {
+        println!("[cmvm] No settings. Use `cmvm config set <key> <value>` to add one.");
+    }
+    Ok(())
+}
+
+pub fn current_version(format: OutputFormat, storage: &impl Storage) -> Result<()> {
+    let report = get_current_report(storage)?;
+
+    if format.is_json() {
+        return output::print_json(&report);
+    }
+
+    match report {
+        CurrentReport {
+            version: Some(version),
+            source: Some(source),
+            ..
+        } => println!("[cmvm] {} (from {})", version, source),
+        CurrentReport {
+            requested: Some(requested),
+            source: Some(source),
+            ..
+        } => println!(
+            "[cmvm] Version {} requested by {} is not installed. Use `cmvm install {}` to install it.",
+            requested, source, requested
+        ),
+        _ => println!(
             "[cmvm] No cmake version is currently active. Use `cmvm use <version>` to set one."
         ),
     }
     Ok(())
 }
 
+fn get_current_report(storage: &impl Storage) -> Result<CurrentReport> {
+    if let Some((request, source)) = project::get_requested_version(&env::current_dir()?)? {
+        let version = resolver::resolve_installed(&request, storage)?;
+        let path = match &version {
+            Some(v) => storage
+                .find_version_dir(&v.get_tag_name())?
+                .map(|(path, _)| path),
+            None => None,
+        };
+        return Ok(CurrentReport {
+            installed: version.is_some(),
+            path,
+            version: version.map(|v| v.get_tag_name()),
+            requested: Some(request),
+            source: Some(source.to_string()),
+        });
+    }
+
+    let source = match multishell::is_active() {
+        true => VersionSource::Session,
+        false => VersionSource::Global,
+    };
+    let target = storage.get_current_version_dir()?.read_link().ok();
+    let version = target
+        .as_ref()
+        .and_then(|path| path.file_name())
+        .map(|name| name.to_string_lossy().to_string());
+
+    Ok(CurrentReport {
+        installed: target.as_ref().is_some_and(|path| path.exists()),
+        source: version.as_ref().map(|_| source.to_string()),
+        requested: None,
+        path: target,
+        version,
+    })
+}
+
 pub fn display_shell_instructions(storage: &impl Storage) -> Result<()> {
     let current_version_dir = storage.get_current_version_dir()?;
 
@@ -113,6 +691,107 @@ pub fn display_shell_instructions(storage: &impl Storage) -> Result<()> {
         "[cmvm] When `cmvm use <version>` is invoked, it changes the `current` symbolic link to the right cmake binary path. As cmvm doesn't manage the `current` path in the system, it requires to manually add it to the $PATH:\n\n export PATH=\"{}/bin:$PATH\"",
         current_version_dir.to_string_lossy()
     );
+    println!(
+        "\n[cmvm] To switch versions automatically from `.cmake-version` files, add this to your shell profile instead:\n\n eval \"$(cmvm env --shell bash)\"   # or zsh, or `cmvm env --shell fish | source`"
+    );
+
+    Ok(())
+}
+
+pub fn display_shell_hook(shell: Shell, multishell: bool, storage: &impl Storage) -> Result<()> {
+    if multishell {
+        let session_link = multishell::create_session_link(storage)?;
+        print!("{}", shell::multishell_env(shell, &session_link));
+    }
+    print!("{}", shell::hook(shell));
+    Ok(())
+}
+
+pub fn display_completions(shell: CompletionShell) -> Result<()> {
+    completions::write_registration(shell, &env::current_exe()?, &mut io::stdout())
+}
+
+pub fn display_bin_path(storage: &impl Storage) -> Result<()> {
+    let bin_dir = shell::resolve_bin_dir(&env::current_dir()?, storage)?;
+    println!("{}", bin_dir.display());
+    Ok(())
+}
+
+/// Regenerates the shims after an install, if `cmvm rehash` set them up.
+fn refresh_shims(storage: &impl Storage) -> Result<()> {
+    if storage.get_shims_dir()?.exists() {
+        shims::rehash(&env::current_exe()?, storage)?;
+    }
+    Ok(())
+}
+
+pub fn rehash_shims(storage: &impl Storage) -> Result<()> {
+    shims::rehash(&env::current_exe()?, storage)?;
+    let shims_dir = storage.get_shims_dir()?;
+
+    println!(
+        "[cmvm] Shims written to {}. Add it to the $PATH to pick the cmake version per directory:\n\n export PATH=\"{}:$PATH\"",
+        shims_dir.display(),
+        shims_dir.display()
+    );
+    Ok(())
+}
+
+pub fn run_shim(name: &str, args: &[OsString], storage: &impl Storage) -> Result<()> {
+    shims::exec(name, args, &env::current_dir()?, storage)
+}
+
+pub fn run_doctor(format: OutputFormat, storage: &impl Storage) -> Result<()> {
+    let problems = doctor::check(&env::var_os("PATH").unwrap_or_default(), storage)?;
+
+    let count = problems.len();
+    if format.is_json() {
+        output::print_json(&DoctorReport {
+            ok: problems.is_empty(),
+            problems,
+        })?;
+    } else if problems.is_empty() {
+        println!("[cmvm] No problems found.");
+    } else {
+        println!("{}", output::format_problems(&problems));
+    }
+
+    if count > 0 {
+        bail!(CmvmError::ProblemsFound(count));
+    }
+    Ok(())
+}
+
+pub fn sync_versions(
+    file: &Path,
+    remove_unlisted: bool,
+    format: OutputFormat,
+    config: &Config,
+    storage: &impl Storage,
+) -> Result<()> {
+    let manifest = Manifest::load(file)?;
+    if !manifest.missing(storage)?.is_empty() {
+        releases::build_cache(config, storage)?;
+    }
+    let report = manifest::sync(&manifest, remove_unlisted, config, storage)?;
+    if !report.installed.is_empty() {
+        refresh_shims(storage)?;
+    }
+
+    if format.is_json() {
+        return output::print_json(&report);
+    }
+
+    let changes = output::format_sync(&report);
+    if !changes.is_empty() {
+        println!("{}", changes);
+    }
+    println!("[cmvm] In sync with {}.", file.display());
+    Ok(())
+}
 
+pub fn freeze_versions(file: &Path, storage: &impl Storage) -> Result<()> {
+    Manifest::freeze(storage)?.save(file)?;
+    println!("[cmvm] Wrote {}.", file.display());
     Ok(())
 }
@@ -0,0 +1,139 @@
+use crate::config::Config;
+use crate::constants::COMPLETE_ENV_VAR;
+use crate::storage::{self, Storage, StorageImpl};
+use crate::{aliases, versions::Version};
+use anyhow::Result;
+use clap::ValueEnum;
+use clap_complete::env::{Bash, Elvish, EnvCompleter, Fish, Zsh};
+use clap_complete::CompletionCandidate;
+use std::io::Write;
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum CompletionShell {
+    Bash,
+    Zsh,
+    Fish,
+    Elvish,
+}
+
+impl CompletionShell {
+    fn completer(&self) -> &'static dyn EnvCompleter {
+        match self {
+            CompletionShell::Bash => &Bash,
+            CompletionShell::Zsh => &Zsh,
+            CompletionShell::Fish => &Fish,
+            CompletionShell::Elvish => &Elvish,
+        }
+    }
+}
+
+/// Writes the script that registers `cmvm` completions with `shell`. The
+/// script calls back into `exe` with `COMPLETE=<shell>` on every <TAB>, so
+/// candidates always reflect what is on disk at that moment.
+pub fn write_registration(shell: CompletionShell, exe: &Path, buf: &mut dyn Write) -> Result<()> {
+    shell.completer().write_registration(
+        COMPLETE_ENV_VAR,
+        "cmvm",
+        "cmvm",
+        &exe.to_string_lossy(),
+        buf,
+    )?;
+    Ok(())
+}
+
+/// Installed version names from `versions/` followed by alias names.
+pub fn installed_versions(storage: &impl Storage) -> Vec<CompletionCandidate> {
+    let versions = Version::all_installed(storage)
+        .unwrap_or_default()
+        .into_iter()
+        .rev()
+        .map(|version| CompletionCandidate::new(version.get_tag_name()));
+    let aliases = aliases::all(storage)
+        .unwrap_or_default()
+        .into_iter()
+        .map(|(name, tag_name)| {
+            CompletionCandidate::new(name).help(Some(format!("alias for {}", tag_name).into()))
+        });
+    versions.chain(aliases).collect()
+}
+
+/// Versions from the cached `releases.json` index. Never touches the network:
+/// without a cache there is simply nothing to offer.
+pub fn remote_versions(storage: &impl Storage) -> Vec<CompletionCandidate> {
+    Version::list_remote(storage)
+        .unwrap_or_default()
+        .into_iter()
+        .rev()
+        .map(|version| CompletionCandidate::new(version.get_tag_name()))
+        .collect()
+}
+
+pub fn installed_candidates() -> Vec<CompletionCandidate> {
+    installed_versions(&configured_storage())
+}
+
+pub fn remote_candidates() -> Vec<CompletionCandidate> {
+    remote_versions(&configured_storage())
+}
+
+fn configured_storage() -> Box<dyn Storage> {
+    storage::from_config(&Config::load().unwrap_or_default())
+        .unwrap_or_else(|_| Box::new(StorageImpl::default()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cache;
+    use crate::storage::test_support::MockStorage;
+    use pretty_assertions::assert_eq;
+    use std::env;
+
+    fn values(candidates: Vec<CompletionCandidate>) -> Vec<String> {
+        candidates
+            .iter()
+            .map(|c| c.get_value().to_string_lossy().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_installed_versions_include_aliases() {
+        let cache_dir = env::temp_dir().join("cmvm_test_completions_installed");
+        let _ = std::fs::remove_dir_all(&cache_dir);
+        for tag in ["3.20.0", "3.28.1"] {
+            std::fs::create_dir_all(cache_dir.join("versions").join(tag)).unwrap();
+        }
+        let storage = MockStorage::new(&cache_dir);
+        aliases::set("legacy", "3.20.0", &storage).unwrap();
+
+        let candidates = values(installed_versions(&storage));
+        cache::delete(&cache_dir).ok();
+
+        assert_eq!(candidates, vec!["3.28.1", "3.20.0", "legacy"]);
+    }
+
+    #[test]
+    fn test_remote_versions_are_empty_without_cache() {
+        let cache_dir = env::temp_dir().join("cmvm_test_completions_no_cache");
+        let _ = std::fs::remove_dir_all(&cache_dir);
+        let storage = MockStorage::new(&cache_dir);
+
+        assert!(remote_versions(&storage).is_empty());
+    }
+
+    #[test]
+    fn test_registration_calls_back_into_exe() {
+        let mut buf = Vec::new();
+        write_registration(
+            CompletionShell::Bash,
+            Path::new("/opt/cmvm/bin/cmvm"),
+            &mut buf,
+        )
+        .unwrap();
+        let script = String::from_utf8(buf).unwrap();
+
+        assert!(script.contains("/opt/cmvm/bin/cmvm"));
+        assert!(script.contains(COMPLETE_ENV_VAR));
+    }
+}
@@ -0,0 +1,517 @@
+use crate::cache;
+use crate::constants::{
+    API_URL_ENV_VAR, BASE_URL, CACHE_DIR_ENV_VAR, DOWNLOAD_REWRITE_ENV_VAR, HOME_ENV_VAR,
+    KEEP_ARCHIVES_ENV_VAR, OFFLINE_ENV_VAR, OUTPUT_ENV_VAR, PRERELEASE_ENV_VAR, SYSTEM_CONFIG_FILE,
+    SYSTEM_VERSIONS_DIR, SYSTEM_VERSIONS_DIR_ENV_VAR, TIMEOUT_ENV_VAR, VERSIONS_DIR_ENV_VAR,
+};
+use crate::output::OutputFormat;
+use crate::storage;
+use anyhow::{anyhow, bail, Context, Result};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Every setting with the environment variable that overrides it.
+pub const SETTINGS: &[(&str, &str)] = &[
+    ("api_url", API_URL_ENV_VAR),
+    ("download_rewrites", DOWNLOAD_REWRITE_ENV_VAR),
+    ("timeout", TIMEOUT_ENV_VAR),
+    ("keep_archives", KEEP_ARCHIVES_ENV_VAR),
+    ("output", OUTPUT_ENV_VAR),
+    ("prerelease", PRERELEASE_ENV_VAR),
+    ("root", HOME_ENV_VAR),
+    ("cache_dir", CACHE_DIR_ENV_VAR),
+    ("versions_dir", VERSIONS_DIR_ENV_VAR),
+    ("system_versions_dir", SYSTEM_VERSIONS_DIR_ENV_VAR),
+    ("offline", OFFLINE_ENV_VAR),
+];
+
+/// cmvm settings. Each layer is a `config.toml`, applied in order: the
+/// system file, the user file in the config directory, then environment
+/// variables. Command-line flags win over all of them.
+///
+/// ```toml
+/// api_url = "https://mirror.example.com/api/repos/Kitware/CMake/releases"
+/// timeout = 60
+/// keep_archives = true
+/// output = "json"
+/// prerelease = false
+/// root = "/srv/cmvm"
+/// cache_dir = "/ci/cache/cmvm"
+/// versions_dir = "/mnt/shared/cmake"
+/// system_versions_dir = "/opt/cmvm/versions"
+/// offline = false
+///
+/// [[download_rewrites]]
+/// from = "https://github.com/Kitware/CMake/releases/download/"
+/// to = "https://mirror.example.com/cmake/"
+/// ```
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct Config {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub api_url: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub download_rewrites: Vec<Rewrite>,
+    /// Seconds before a release index request, or connecting for a
+    /// download, is abandoned.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout: Option<u64>,
+    /// Keep downloaded archives in `<cache_dir>/archives` for reinstalls.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub keep_archives: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output: Option<OutputFormat>,
+    /// Let `install` and `list-remote` consider release candidates.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prerelease: Option<bool>,
+    /// Directory holding versions, `current` and the cache instead of the
+    /// OS-standard directories.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub root: Option<PathBuf>,
+    /// Cache directory, overriding the one implied by `root`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache_dir: Option<PathBuf>,
+    /// Directory of installed versions, overriding the one implied by `root`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub versions_dir: Option<PathBuf>,
+    /// Shared versions installed with `install --system`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub system_versions_dir: Option<PathBuf>,
+    /// Never touch the network: use the cached `releases.json` and kept
+    /// archives only.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub offline: Option<bool>,
+}
+
+/// Replaces the `from` prefix of a download URL with `to`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Rewrite {
+    pub from: String,
+    pub to: String,
+}
+
+/// The config files read by `Config::load`, lowest precedence first.
+pub fn config_files() -> Result<Vec<PathBuf>> {
+    Ok(vec![
+        PathBuf::from(SYSTEM_CONFIG_FILE),
+        storage::user_config_file()?,
+    ])
+}
+
+impl Config {
+    pub fn load() -> Result<Config> {
+        Config::load_layers(&config_files()?, |name| env::var(name).ok())
+    }
+
+    /// Merges `files` in order, skipping missing ones, then the values
+    /// returned by `env` for each setting's environment variable.
+    pub fn load_layers(files: &[PathBuf], env: impl Fn(&str) -> Option<String>) -> Result<Config> {
+        let mut config = Config::default();
+        for path in files.iter().filter(|path| path.exists()) {
+            config.merge(Config::read(path)?);
+        }
+
+        let mut from_env = Config::default();
+        for (key, name) in SETTINGS {
+            if let Some(value) = env(name).filter(|value| !value.trim().is_empty()) {
+                from_env
+                    .set(key, value.trim())
+                    .with_context(|| format!("[cmvm] Invalid {}", name))?;
+            }
+        }
+        config.merge(from_env);
+        Ok(config)
+    }
+
+    /// Reads a single config file, or an empty config when it does not exist.
+    pub fn read(path: &Path) -> Result<Config> {
+        if !path.exists() {
+            return Ok(Config::default());
+        }
+        toml::from_str(&cache::open_file(path.to_path_buf())?)
+            .with_context(|| format!("[cmvm] Failed to parse {}", path.display()))
+    }
+
+    /// Applies the values set in `other` on top of this config. Rewrite
+    /// rules of `other` are tried before the existing ones.
+    fn merge(&mut self, other: Config) {
+        let Config {
+            api_url,
+            mut download_rewrites,
+            timeout,
+            keep_archives,
+            output,
+            prerelease,
+            root,
+            cache_dir,
+            versions_dir,
+            system_versions_dir,
+            offline,
+        } = other;
+        self.api_url = api_url.or(self.api_url.take());
+        download_rewrites.append(&mut self.download_rewrites);
+        self.download_rewrites = download_rewrites;
+        self.timeout = timeout.or(self.timeout);
+        self.keep_archives = keep_archives.or(self.keep_archives);
+        self.output = output.or(self.output);
+        self.prerelease = prerelease.or(self.prerelease);
+        self.root = root.or(self.root.take());
+        self.cache_dir = cache_dir.or(self.cache_dir.take());
+        self.versions_dir = versions_dir.or(self.versions_dir.take());
+        self.system_versions_dir = system_versions_dir.or(self.system_versions_dir.take());
+        self.offline = offline.or(self.offline);
+    }
+
+    /// Sets `key` from its textual form, as given to `cmvm config set` or in
+    /// an environment variable.
+    pub fn set(&mut self, key: &str, value: &str) -> Result<()> {
+        match key {
+            "api_url" => self.api_url = Some(value.to_string()),
+            "download_rewrites" => {
+                self.download_rewrites = value
+                    .split_whitespace()
+                    .map(parse_rewrite)
+                    .collect::<Result<_>>()?
+            }
+            "timeout" => {
+                self.timeout = Some(value.parse().map_err(|_| {
+                    anyhow!("[cmvm] Invalid timeout `{}`, expected seconds.", value)
+                })?)
+            }
+            "keep_archives" => self.keep_archives = Some(parse_bool(value)?),
+            "output" => {
+                self.output = Some(OutputFormat::from_str(value, true).map_err(|_| {
+                    anyhow!("[cmvm] Invalid output `{}`, expected text or json.", value)
+                })?)
+            }
+            "prerelease" => self.prerelease = Some(parse_bool(value)?),
+            "root" => self.root = Some(PathBuf::from(value)),
+            "cache_dir" => self.cache_dir = Some(PathBuf::from(value)),
+            "versions_dir" => self.versions_dir = Some(PathBuf::from(value)),
+            "system_versions_dir" => self.system_versions_dir = Some(PathBuf::from(value)),
+            "offline" => self.offline = Some(parse_bool(value)?),
+            _ => bail!(unknown_key(key)),
+        }
+        Ok(())
+    }
+
+    pub fn unset(&mut self, key: &str) -> Result<()> {
+        match key {
+            "api_url" => self.api_url = None,
+            "download_rewrites" => self.download_rewrites.clear(),
+            "timeout" => self.timeout = None,
+            "keep_archives" => self.keep_archives = None,
+            "output" => self.output = None,
+            "prerelease" => self.prerelease = None,
+            "root" => self.root = None,
+            "cache_dir" => self.cache_dir = None,
+            "versions_dir" => self.versions_dir = None,
+            "system_versions_dir" => self.system_versions_dir = None,
+            "offline" => self.offline = None,
+            _ => bail!(unknown_key(key)),
+        }
+        Ok(())
+    }
+
+    /// The textual form of `key`, in the syntax accepted by `set`, or `None`
+    /// when it is not set.
+    pub fn get(&self, key: &str) -> Result<Option<String>> {
+        Ok(match key {
+            "api_url" => self.api_url.clone(),
+            "download_rewrites" => Some(
+                self.download_rewrites
+                    .iter()
+                    .map(|rule| format!("{}={}", rule.from, rule.to))
+                    .collect::<Vec<_>>()
+                    .join(" "),
+            )
+            .filter(|rules| !rules.is_empty()),
+            "timeout" => self.timeout.map(|t| t.to_string()),
+            "keep_archives" => self.keep_archives.map(|b| b.to_string()),
+            "output" => self
+                .output
+                .and_then(|f| f.to_possible_value())
+                .map(|v| v.get_name().to_string()),
+            "prerelease" => self.prerelease.map(|b| b.to_string()),
+            "root" => self.root.as_ref().map(|p| p.display().to_string()),
+            "cache_dir" => self.cache_dir.as_ref().map(|p| p.display().to_string()),
+            "versions_dir" => self.versions_dir.as_ref().map(|p| p.display().to_string()),
+            "system_versions_dir" => self
+                .system_versions_dir
+                .as_ref()
+                .map(|p| p.display().to_string()),
+            "offline" => self.offline.map(|b| b.to_string()),
+            _ => bail!(unknown_key(key)),
+        })
+    }
+
+    /// The releases endpoint of the GitHub API or of its configured mirror.
+    pub fn api_url(&self) -> &str {
+        self.api_url
+            .as_deref()
+            .map(|url| url.trim_end_matches('/'))
+            .unwrap_or(BASE_URL)
+    }
+
+    /// Applies the first rewrite rule whose `from` prefixes `url`.
+    pub fn rewrite_download_url(&self, url: &str) -> String {
+        self.download_rewrites
+            .iter()
+            .find_map(|rule| {
+                url.strip_prefix(rule.from.as_str())
+                    .map(|rest| format!("{}{}", rule.to, rest))
+            })
+            .unwrap_or_else(|| url.to_string())
+    }
+
+    pub fn timeout(&self) -> Option<Duration> {
+        self.timeout.map(Duration::from_secs)
+    }
+
+    pub fn keep_archives(&self) -> bool {
+        self.keep_archives.unwrap_or(false)
+    }
+
+    pub fn prerelease(&self) -> bool {
+        self.prerelease.unwrap_or(false)
+    }
+
+    pub fn offline(&self) -> bool {
+        self.offline.unwrap_or(false)
+    }
+
+    pub fn system_versions_dir(&self) -> PathBuf {
+        self.system_versions_dir
+            .clone()
+            .unwrap_or_else(|| PathBuf::from(SYSTEM_VERSIONS_DIR))
+    }
+}
+
+/// Writes `key` to the config file at `path`. The file is edited as plain
+/// TOML, so the other settings in it are kept as they are, even invalid ones
+/// that `cmvm config` is being used to fix.
+pub fn set_in_file(path: &Path, key: &str, value: &str) -> Result<()> {
+    let mut setting = Config::default();
+    setting.set(key, value)?;
+
+    let mut table = read_table(path)?;
+    table.remove(key);
+    if let toml::Value::Table(values) = toml::Value::try_from(&setting)? {
+        table.extend(values);
+    }
+    write_table(path, &table)
+}
+
+/// Removes `key` from the config file at `path`, like `set_in_file`.
+pub fn unset_in_file(path: &Path, key: &str) -> Result<()> {
+    Config::default().unset(key)?;
+
+    let mut table = read_table(path)?;
+    table.remove(key);
+    write_table(path, &table)
+}
+
+fn read_table(path: &Path) -> Result<toml::Table> {
+    if !path.exists() {
+        return Ok(toml::Table::new());
+    }
+    toml::from_str(&cache::open_file(path.to_path_buf())?)
+        .with_context(|| format!("[cmvm] Failed to parse {}", path.display()))
+}
+
+fn write_table(path: &Path, table: &toml::Table) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let mut file = cache::create_file(path)?;
+    file.write_all(toml::to_string(table)?.as_bytes())?;
+    Ok(())
+}
+
+fn unknown_key(key: &str) -> anyhow::Error {
+    anyhow!(
+        "[cmvm] Unknown setting `{}`. Available settings: {}.",
+        key,
+        SETTINGS
+            .iter()
+            .map(|(key, _)| *key)
+            .collect::<Vec<_>>()
+            .join(", ")
+    )
+}
+
+fn parse_bool(value: &str) -> Result<bool> {
+    match value.to_lowercase().as_str() {
+        "true" | "1" | "yes" => Ok(true),
+        "false" | "0" | "no" => Ok(false),
+        _ => bail!("[cmvm] Invalid value `{}`, expected true or false.", value),
+    }
+}
+
+fn parse_rewrite(rule: &str) -> Result<Rewrite> {
+    let (from, to) = rule
+        .split_once('=')
+        .filter(|(from, to)| !from.is_empty() && !to.is_empty())
+        .ok_or_else(|| anyhow!("[cmvm] Invalid rewrite rule `{}`, expected FROM=TO.", rule))?;
+    Ok(Rewrite {
+        from: from.to_string(),
+        to: to.to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use std::collections::HashMap;
+
+    fn mirror_config() -> Config {
+        Config {
+            api_url: Some("https://mirror.example.com/api/releases/".to_string()),
+            download_rewrites: vec![Rewrite {
+                from: "https://github.com/Kitware/CMake/releases/download/".to_string(),
+                to: "https://mirror.example.com/cmake/".to_string(),
+            }],
+            timeout: Some(60),
+            ..Config::default()
+        }
+    }
+
+    fn no_env(_: &str) -> Option<String> {
+        None
+    }
+
+    #[test]
+    fn test_defaults_to_github() {
+        let config = Config::default();
+        let url = "https://github.com/Kitware/CMake/releases/download/v3.28.1/cmake.tar.gz";
+
+        assert_eq!(config.api_url(), BASE_URL);
+        assert_eq!(config.rewrite_download_url(url), url);
+        assert_eq!(config.timeout(), None);
+        assert!(!config.keep_archives());
+    }
+
+    #[test]
+    fn test_rewrite_download_url_replaces_prefix() {
+        let config = mirror_config();
+
+        assert_eq!(config.api_url(), "https://mirror.example.com/api/releases");
+        assert_eq!(
+            config.rewrite_download_url(
+                "https://github.com/Kitware/CMake/releases/download/v3.28.1/cmake.tar.gz"
+            ),
+            "https://mirror.example.com/cmake/v3.28.1/cmake.tar.gz"
+        );
+        assert_eq!(
+            config.rewrite_download_url("https://example.org/cmake.tar.gz"),
+            "https://example.org/cmake.tar.gz"
+        );
+    }
+
+    #[test]
+    fn test_layers_apply_in_order() {
+        let dir = env::temp_dir().join("cmvm_test_config_layers");
+        let _ = std::fs::remove_dir_all(&dir);
+        let system = dir.join("system.toml");
+        let user = dir.join("user.toml");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(&system, toml::to_string(&mirror_config()).unwrap()).unwrap();
+        std::fs::write(&user, "timeout = 5\noutput = \"json\"\n").unwrap();
+        let env = HashMap::from([
+            (API_URL_ENV_VAR, "https://other.example.com/releases"),
+            (
+                DOWNLOAD_REWRITE_ENV_VAR,
+                "https://github.com/=https://proxy.example.com/",
+            ),
+            (KEEP_ARCHIVES_ENV_VAR, "1"),
+        ]);
+
+        let config = Config::load_layers(&[system, user, dir.join("missing.toml")], |name| {
+            env.get(name).map(|value| value.to_string())
+        })
+        .unwrap();
+        cache::delete(&dir).ok();
+
+        assert_eq!(config.api_url(), "https://other.example.com/releases");
+        assert_eq!(config.timeout(), Some(Duration::from_secs(5)));
+        assert_eq!(config.output, Some(OutputFormat::Json));
+        assert!(config.keep_archives());
+        assert_eq!(
+            config.rewrite_download_url(
+                "https://github.com/Kitware/CMake/releases/download/v3.28.1/cmake.tar.gz"
+            ),
+            "https://proxy.example.com/Kitware/CMake/releases/download/v3.28.1/cmake.tar.gz"
+        );
+        assert_eq!(config.download_rewrites.len(), 2);
+    }
+
+    #[test]
+    fn test_invalid_env_value_is_an_error() {
+        let result = Config::load_layers(&[], |name| {
+            (name == TIMEOUT_ENV_VAR).then(|| "soon".to_string())
+        });
+
+        assert!(result.is_err());
+        assert_eq!(Config::load_layers(&[], no_env).unwrap(), Config::default());
+    }
+
+    #[test]
+    fn test_set_get_and_unset() {
+        let mut config = Config::default();
+        config.set("output", "JSON").unwrap();
+        config.set("keep_archives", "yes").unwrap();
+        config
+            .set(
+                "download_rewrites",
+                "https://a/=https://b/ https://c/=https://d/",
+            )
+            .unwrap();
+
+        assert_eq!(config.get("output").unwrap(), Some("json".to_string()));
+        assert_eq!(
+            config.get("keep_archives").unwrap(),
+            Some("true".to_string())
+        );
+        assert_eq!(
+            config.get("download_rewrites").unwrap(),
+            Some("https://a/=https://b/ https://c/=https://d/".to_string())
+        );
+        assert!(config.set("timeout", "-1").is_err());
+        assert!(config.set("download_rewrites", "https://a/").is_err());
+        assert!(config.get("colour").is_err());
+
+        config.unset("output").unwrap();
+        assert_eq!(config.get("output").unwrap(), None);
+    }
+
+    #[test]
+    fn test_set_and_unset_in_file_keep_invalid_settings() {
+        let dir = env::temp_dir().join("cmvm_test_config_file_edit");
+        let _ = std::fs::remove_dir_all(&dir);
+        let path = dir.join("config.toml");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(&path, "timeout = \"x\"\nroot = \"/srv/cmvm\"\n").unwrap();
+
+        set_in_file(&path, "output", "json").unwrap();
+        let with_output = Config::read(&path);
+        unset_in_file(&path, "timeout").unwrap();
+        let fixed = Config::read(&path).unwrap();
+        let unknown = unset_in_file(&path, "colour");
+        cache::delete(&dir).ok();
+
+        assert!(with_output.is_err());
+        assert_eq!(
+            fixed,
+            Config {
+                output: Some(OutputFormat::Json),
+                root: Some(PathBuf::from("/srv/cmvm")),
+                ..Config::default()
+            }
+        );
+        assert!(unknown.is_err());
+    }
+}
@@ -3,3 +3,63 @@ pub const SUPPORTED_PLATFORMS: &[&str] = &["macos", "linux"];
 pub const BASE_URL: &str = "https://api.github.com/repos/Kitware/CMake/releases";
 
 pub const RELEASES_FILE_NAME: &str = "releases.json";
+
+pub const VERSION_FILE_NAME: &str = ".cmake-version";
+
+pub const VERSION_ENV_VAR: &str = "CMVM_VERSION";
+
+pub const CMAKE_LISTS_FILE_NAME: &str = "CMakeLists.txt";
+
+pub const MULTISHELL_ENV_VAR: &str = "CMVM_MULTISHELL_PATH";
+
+pub const MULTISHELLS_DIR_NAME: &str = "multishells";
+
+pub const SHIMS_DIR_NAME: &str = "shims";
+
+pub const SHIM_NAMES: &[&str] = &["cmake", "ctest", "cpack", "ccmake", "cmake-gui"];
+
+pub const ALIASES_DIR_NAME: &str = "aliases";
+
+pub const MANIFEST_FILE_NAME: &str = "cmvm.toml";
+
+pub const COMPLETE_ENV_VAR: &str = "COMPLETE";
+
+pub const FROM_FILE_STAGING_DIR: &str = "from-file";
+
+pub const FROM_SOURCE_STAGING_DIR: &str = "from-source";
+
+pub const LOGS_DIR_NAME: &str = "logs";
+
+pub const CMAKE_GIT_URL: &str = "https://github.com/Kitware/CMake.git";
+
+pub const CONFIG_FILE_NAME: &str = "config.toml";
+
+pub const API_URL_ENV_VAR: &str = "CMVM_API_URL";
+
+pub const DOWNLOAD_REWRITE_ENV_VAR: &str = "CMVM_DOWNLOAD_REWRITE";
+
+pub const TIMEOUT_ENV_VAR: &str = "CMVM_TIMEOUT";
+
+pub const KEEP_ARCHIVES_ENV_VAR: &str = "CMVM_KEEP_ARCHIVES";
+
+pub const OUTPUT_ENV_VAR: &str = "CMVM_OUTPUT";
+
+pub const PRERELEASE_ENV_VAR: &str = "CMVM_PRERELEASE";
+
+pub const HOME_ENV_VAR: &str = "CMVM_HOME";
+
+pub const CACHE_DIR_ENV_VAR: &str = "CMVM_CACHE_DIR";
+
+pub const VERSIONS_DIR_ENV_VAR: &str = "CMVM_VERSIONS_DIR";
+
+pub const SYSTEM_VERSIONS_DIR: &str = "/opt/cmvm/versions";
+
+pub const SYSTEM_VERSIONS_DIR_ENV_VAR: &str = "CMVM_SYSTEM_VERSIONS_DIR";
+
+pub const OFFLINE_ENV_VAR: &str = "CMVM_OFFLINE";
+
+pub const SYSTEM_CONFIG_FILE: &str = "/etc/cmvm/config.toml";
+
+pub const ARCHIVES_DIR_NAME: &str = "archives";
+
+pub const CACHE_DIR_NAME: &str = "cache";
@@ -0,0 +1,240 @@
+use crate::cache;
+use crate::constants::CMAKE_LISTS_FILE_NAME;
+use crate::package;
+use crate::resolver::VersionRequest;
+use crate::storage::Storage;
+use crate::versions::Version;
+use anyhow::{bail, Result};
+use std::path::Path;
+
+/// Versions declared by `cmake_minimum_required(VERSION <min>[...<max>])`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MinimumRequired {
+    pub min: String,
+    pub max: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Detection {
+    pub version: Version,
+    pub installed: bool,
+    pub reason: String,
+}
+
+pub fn parse_minimum_required(contents: &str) -> Option<MinimumRequired> {
+    let code: String = contents
+        .lines()
+        .map(|line| line.split('#').next().unwrap_or(""))
+        .collect::<Vec<&str>>()
+        .join("\n");
+
+    let arguments = find_command_arguments(&code, "cmake_minimum_required")?;
+
+    let mut tokens = arguments.split_whitespace();
+    tokens.find(|token| token.eq_ignore_ascii_case("VERSION"))?;
+    let range = tokens.next()?;
+
+    let mut bounds = range.splitn(2, "...");
+    let min = truncate_version(bounds.next()?)?;
+    let max = bounds.next().and_then(truncate_version);
+
+    Some(MinimumRequired { min, max })
+}
+
+pub fn read_minimum_required(project_dir: &Path) -> Result<MinimumRequired> {
+    let cmake_lists = project_dir.join(CMAKE_LISTS_FILE_NAME);
+    if !cmake_lists.exists() {
+        bail!(
+            "[cmvm] No {} found in {}.",
+            CMAKE_LISTS_FILE_NAME,
+            project_dir.display()
+        );
+    }
+
+    match parse_minimum_required(&cache::open_file(cmake_lists.clone())?) {
+        Some(minimum_required) => Ok(minimum_required),
+        None => bail!(
+            "[cmvm] No `cmake_minimum_required(VERSION ...)` call found in {}.",
+            cmake_lists.display()
+        ),
+    }
+}
+
+/// Picks the version that best fits the project: the newest release within
+/// the policy range when a max is given, otherwise the newest release of the
+/// same major version at or above the minimum. Installed versions win over
+/// remote ones.
+pub fn detect(project_dir: &Path, storage: &impl Storage) -> Result<Detection> {
+    let minimum_required = read_minimum_required(project_dir)?;
+
+    let (request, explanation) = match &minimum_required.max {
+        Some(max) => (
+            format!(">={},<={}", minimum_required.min, max),
+            format!("the policy max {}", max),
+        ),
+        None => (
+            format!("^{}", minimum_required.min),
+            format!("the minimum {}", minimum_required.min),
+        ),
+    };
+    let request = VersionRequest::parse(&request)?;
+
+    if let Some(version) = request.best_match(Version::all_installed(storage)?) {
+        return Ok(Detection {
+            reason: format!(
+                "using the installed {} for {}",
+                version.get_tag_name(),
+                explanation
+            ),
+            version,
+            installed: true,
+        });
+    }
+
+    let remote: Vec<Version> = Version::all_from_cache(storage)?
+        .into_iter()
+        .filter(|v| !package::filter_platform_assets(v).is_empty())
+        .collect();
+
+    match request.best_match(remote) {
+        Some(version) => Ok(Detection {
+            reason: format!("using {} for {}", version.get_tag_name(), explanation),
+            version,
+            installed: false,
+        }),
+        None => bail!(
+            "[cmvm] No cmake version matches {} required by {}.",
+            explanation,
+            CMAKE_LISTS_FILE_NAME
+        ),
+    }
+}
+
+/// The text between the parentheses of the first call to `command`. Only a
+/// name that starts a statement counts, so mentions inside strings or other
+/// calls are skipped.
+fn find_command_arguments<'a>(code: &'a str, command: &str) -> Option<&'a str> {
+    // ASCII lowercasing keeps byte offsets valid for slicing `code`
+    let lowercase = code.to_ascii_lowercase();
+    let mut offset = 0;
+    while let Some(found) = lowercase[offset..].find(command) {
+        let start = offset + found;
+        let end = start + command.len();
+        offset = end;
+
+        // the previous statement ends with `)`, anything else means the
+        // name is an argument
+        let starts_statement = matches!(
+            lowercase[..start].trim_end().chars().last(),
+            None | Some(')')
+        );
+        let rest = code[end..].trim_start();
+        if !starts_statement || !rest.starts_with('(') {
+            continue;
+        }
+        let arguments = &rest[1..];
+        return arguments.find(')').map(|close| &arguments[..close]);
+    }
+    None
+}
+
+/// Keeps at most `major.minor.patch`, as `cmake_minimum_required` accepts a
+/// fourth tweak component that releases never use.
+fn truncate_version(version: &str) -> Option<String> {
+    let version = version.trim();
+    if version.is_empty() {
+        return None;
+    }
+    Some(version.split('.').take(3).collect::<Vec<&str>>().join("."))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::test_support::MockStorage;
+
+    use pretty_assertions::assert_eq;
+    use std::env;
+    use std::fs;
+
+    #[test]
+    fn test_parse_minimum_required_min_only() {
+        let contents = "# cmake_minimum_required(VERSION 2.8)\ncmake_minimum_required(VERSION 3.16 FATAL_ERROR)\nproject(foo)\n";
+        assert_eq!(
+            parse_minimum_required(contents),
+            Some(MinimumRequired {
+                min: "3.16".to_string(),
+                max: None
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_minimum_required_policy_range() {
+        let contents = "CMAKE_MINIMUM_REQUIRED(\n  VERSION 3.16.0.1...3.27\n)\n";
+        assert_eq!(
+            parse_minimum_required(contents),
+            Some(MinimumRequired {
+                min: "3.16.0".to_string(),
+                max: Some("3.27".to_string())
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_minimum_required_skips_mentions_in_arguments() {
+        let contents = "message(STATUS \"needs cmake_minimum_required\")\nproject(foo)\ncmake_minimum_required(VERSION 3.20)\n";
+        assert_eq!(
+            parse_minimum_required(contents),
+            Some(MinimumRequired {
+                min: "3.20".to_string(),
+                max: None
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_minimum_required_after_non_ascii_text() {
+        let contents =
+            "project(\u{212a}\u{23a}\u{212a}\u{23a})\ncmake_minimum_required(VERSION 3.21)\n";
+        assert_eq!(
+            parse_minimum_required(contents),
+            Some(MinimumRequired {
+                min: "3.21".to_string(),
+                max: None
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_minimum_required_missing() {
+        assert_eq!(parse_minimum_required("project(foo)\n"), None);
+    }
+
+    #[test]
+    fn test_detect_prefers_installed_within_policy_range() {
+        let cache_dir = env::temp_dir().join("cmvm_test_detect_installed");
+        let _ = fs::remove_dir_all(&cache_dir);
+        for tag in ["3.20.0", "3.27.9", "3.28.1"] {
+            fs::create_dir_all(cache_dir.join("versions").join(tag)).unwrap();
+        }
+        let project_dir = cache_dir.join("project");
+        fs::create_dir_all(&project_dir).unwrap();
+        fs::write(
+            project_dir.join(CMAKE_LISTS_FILE_NAME),
+            "cmake_minimum_required(VERSION 3.16...3.27)\n",
+        )
+        .unwrap();
+        let storage = MockStorage::new(&cache_dir);
+
+        let detection = detect(&project_dir, &storage).unwrap();
+        let _ = fs::remove_dir_all(&cache_dir);
+
+        assert_eq!(detection.version.get_tag_name(), "3.27.9");
+        assert!(detection.installed);
+        assert_eq!(
+            detection.reason,
+            "using the installed 3.27.9 for the policy max 3.27"
+        );
+    }
+}
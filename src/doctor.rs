@@ -0,0 +1,311 @@
+use crate::cache;
+use crate::constants::{
+    ARCHIVES_DIR_NAME, LOGS_DIR_NAME, MULTISHELLS_DIR_NAME, RELEASES_FILE_NAME,
+};
+use crate::storage::Storage;
+use crate::versions::Version;
+use anyhow::Result;
+use serde::Serialize;
+use std::env;
+use std::ffi::OsStr;
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Problem {
+    pub message: String,
+    pub fix: String,
+}
+
+impl Problem {
+    fn new(message: String, fix: String) -> Problem {
+        Problem { message, fix }
+    }
+}
+
+/// Runs every check against `storage` and the given PATH value.
+pub fn check(path: &OsStr, storage: &impl Storage) -> Result<Vec<Problem>> {
+    let mut problems = Vec::new();
+    problems.extend(check_path(path, storage)?);
+    problems.extend(check_current(storage)?);
+    problems.extend(check_releases(storage)?);
+    problems.extend(check_cache_leftovers(storage)?);
+    problems.extend(check_installed(storage)?);
+    Ok(problems)
+}
+
+fn is_executable(path: &Path) -> bool {
+    path.metadata()
+        .map(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+        .unwrap_or(false)
+}
+
+/// `current/bin` (or the shims, or a version dir set by the shell hook) must be
+/// on PATH, ahead of any other cmake installation.
+fn check_path(path: &OsStr, storage: &impl Storage) -> Result<Vec<Problem>> {
+    let current_bin_dir = storage.get_current_version_dir()?.join("bin");
+    let shims_dir = storage.get_shims_dir()?;
+    let versions_dir = storage.get_versions_dir()?;
+    let system_versions_dir = storage.get_system_versions_dir()?;
+    let is_managed = |dir: &Path| {
+        dir == current_bin_dir
+            || dir == shims_dir
+            || dir.starts_with(&versions_dir)
+            || system_versions_dir
+                .as_ref()
+                .is_some_and(|system| dir.starts_with(system))
+    };
+
+    let dirs: Vec<_> = env::split_paths(path).collect();
+    if !dirs.iter().any(|dir| is_managed(dir)) {
+        return Ok(vec![Problem::new(
+            format!("{} is not on PATH.", current_bin_dir.display()),
+            format!(
+                "Add `export PATH=\"{}:$PATH\"` to your shell profile, or run `cmvm shell` for more options.",
+                current_bin_dir.display()
+            ),
+        )]);
+    }
+
+    let first_cmake_dir = dirs
+        .iter()
+        .find(|dir| is_managed(dir) || is_executable(&dir.join("cmake")));
+    match first_cmake_dir {
+        Some(dir) if !is_managed(dir) => Ok(vec![Problem::new(
+            format!(
+                "{} comes before cmvm on PATH, so cmvm cannot switch cmake.",
+                dir.join("cmake").display()
+            ),
+            format!(
+                "Move {} before {} in PATH, or uninstall the other cmake.",
+                current_bin_dir.display(),
+                dir.display()
+            ),
+        )]),
+        _ => Ok(vec![]),
+    }
+}
+
+fn check_current(storage: &impl Storage) -> Result<Vec<Problem>> {
+    let current_version_dir = storage.get_current_version_dir()?;
+    let versions_dir = storage.get_versions_dir()?;
+
+    let Ok(target) = current_version_dir.read_link() else {
+        return Ok(vec![]);
+    };
+
+    let in_system_store = storage
+        .get_system_versions_dir()?
+        .is_some_and(|system| target.starts_with(system));
+    if !target.starts_with(&versions_dir) && !in_system_store {
+        return Ok(vec![Problem::new(
+            format!(
+                "{} points outside {}: {}.",
+                current_version_dir.display(),
+                versions_dir.display(),
+                target.display()
+            ),
+            "Run `cmvm use <version>` to point it at an installed version.".to_string(),
+        )]);
+    }
+
+    if !target.exists() {
+        return Ok(vec![Problem::new(
+            format!(
+                "{} is dangling: {} does not exist.",
+                current_version_dir.display(),
+                target.display()
+            ),
+            "Run `cmvm use <version>` to point it at an installed version.".to_string(),
+        )]);
+    }
+
+    Ok(vec![])
+}
+
+fn check_releases(storage: &impl Storage) -> Result<Vec<Problem>> {
+    let releases_file = storage.get_cache_dir()?.join(RELEASES_FILE_NAME);
+    if !releases_file.exists() {
+        return Ok(vec![]);
+    }
+
+    match Version::all_from_cache(storage) {
+        Ok(_) => Ok(vec![]),
+        Err(e) => Ok(vec![Problem::new(
+            format!("{} cannot be parsed: {}.", releases_file.display(), e),
+            format!(
+                "Delete {} and run `cmvm list-remote` to fetch it again.",
+                releases_file.display()
+            ),
+        )]),
+    }
+}
+
+/// Page files and staging dirs are removed once a fetch or an install
+/// finishes, so any left behind come from an interrupted run.
+fn check_cache_leftovers(storage: &impl Storage) -> Result<Vec<Problem>> {
+    let cache_dir = storage.get_cache_dir()?;
+    if !cache_dir.exists() {
+        return Ok(vec![]);
+    }
+
+    let mut leftovers: Vec<_> = cache::ls(&cache_dir)?
+        .into_iter()
+        .filter(|path| {
+            let name = path.file_name().unwrap_or_default().to_string_lossy();
+            let is_page = name
+                .strip_suffix(".json")
+                .is_some_and(|page| page.parse::<u32>().is_ok());
+            let is_staging = path.is_dir()
+                && ![MULTISHELLS_DIR_NAME, LOGS_DIR_NAME, ARCHIVES_DIR_NAME]
+                    .contains(&name.as_ref());
+            is_page || is_staging
+        })
+        .collect();
+    leftovers.sort();
+
+    Ok(leftovers
+        .into_iter()
+        .map(|path| {
+            Problem::new(
+                format!("Leftover from an interrupted download: {}.", path.display()),
+                format!("Delete {}.", path.display()),
+            )
+        })
+        .collect())
+}
+
+fn check_installed(storage: &impl Storage) -> Result<Vec<Problem>> {
+    let versions_dir = storage.get_versions_dir()?;
+    if !versions_dir.exists() {
+        return Ok(vec![]);
+    }
+
+    let mut broken = Vec::new();
+    for version in Version::all_installed(storage)? {
+        if let Some((version_dir, _)) = storage.find_version_dir(&version.get_tag_name())? {
+            if !is_executable(&version_dir.join("bin").join("cmake")) {
+                broken.push(version);
+            }
+        }
+    }
+
+    Ok(broken
+        .into_iter()
+        .map(|version| {
+            Problem::new(
+                format!(
+                    "Version {} has no executable bin/cmake.",
+                    version.get_tag_name()
+                ),
+                format!(
+                    "Run `cmvm uninstall {}` and `cmvm install {}` to reinstall it.",
+                    version.get_tag_name(),
+                    version.get_tag_name()
+                ),
+            )
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::test_support::{storage_in, MockStorage};
+    use pretty_assertions::assert_eq;
+    use std::fs;
+    use std::path::PathBuf;
+
+    fn install_fake(storage: &MockStorage, tag: &str, mode: u32) -> PathBuf {
+        let bin_dir = storage.root.join("versions").join(tag).join("bin");
+        fs::create_dir_all(&bin_dir).unwrap();
+        fs::write(bin_dir.join("cmake"), "#!/bin/sh\n").unwrap();
+        fs::set_permissions(bin_dir.join("cmake"), fs::Permissions::from_mode(mode)).unwrap();
+        bin_dir
+    }
+
+    #[test]
+    fn test_check_path_reports_missing_current_bin() {
+        let storage = storage_in("cmvm_test_doctor_path_missing");
+        let problems = check_path(OsStr::new("/usr/bin:/bin"), &storage).unwrap();
+        let _ = fs::remove_dir_all(&storage.root);
+
+        assert_eq!(problems.len(), 1);
+        assert!(problems[0].message.contains("is not on PATH"));
+    }
+
+    #[test]
+    fn test_check_path_reports_shadowing_cmake() {
+        let storage = storage_in("cmvm_test_doctor_path_shadow");
+        let other_bin = storage.root.join("other");
+        fs::create_dir_all(&other_bin).unwrap();
+        fs::write(other_bin.join("cmake"), "").unwrap();
+        fs::set_permissions(other_bin.join("cmake"), fs::Permissions::from_mode(0o755)).unwrap();
+        let current_bin = storage.root.join("current").join("bin");
+        let path = env::join_paths([other_bin.clone(), current_bin.clone()]).unwrap();
+        let reversed = env::join_paths([current_bin, other_bin]).unwrap();
+
+        let problems = check_path(&path, &storage).unwrap();
+        let no_problems = check_path(&reversed, &storage).unwrap();
+        let _ = fs::remove_dir_all(&storage.root);
+
+        assert_eq!(problems.len(), 1);
+        assert!(problems[0].message.contains("comes before cmvm"));
+        assert_eq!(no_problems, vec![]);
+    }
+
+    #[test]
+    fn test_check_current_reports_dangling_and_outside_links() {
+        let storage = storage_in("cmvm_test_doctor_current");
+        let current = storage.root.join("current");
+        std::os::unix::fs::symlink(storage.root.join("versions/3.99.0"), &current).unwrap();
+        let dangling = check_current(&storage).unwrap();
+        fs::remove_file(&current).unwrap();
+        std::os::unix::fs::symlink("/usr", &current).unwrap();
+        let outside = check_current(&storage).unwrap();
+        let _ = fs::remove_dir_all(&storage.root);
+
+        assert!(dangling[0].message.contains("is dangling"));
+        assert!(outside[0].message.contains("points outside"));
+    }
+
+    #[test]
+    fn test_check_releases_reports_invalid_json() {
+        let storage = storage_in("cmvm_test_doctor_releases");
+        fs::write(storage.cache_dir.join(RELEASES_FILE_NAME), "{not json").unwrap();
+        let problems = check_releases(&storage).unwrap();
+        let _ = fs::remove_dir_all(&storage.root);
+
+        assert_eq!(problems.len(), 1);
+    }
+
+    #[test]
+    fn test_check_cache_leftovers_finds_pages_and_staging_dirs() {
+        let storage = storage_in("cmvm_test_doctor_leftovers");
+        let cache_dir = storage.cache_dir.clone();
+        fs::write(cache_dir.join("2.json"), "[]").unwrap();
+        fs::write(cache_dir.join(RELEASES_FILE_NAME), "[]").unwrap();
+        fs::create_dir_all(cache_dir.join("3.28.1")).unwrap();
+        fs::create_dir_all(cache_dir.join(MULTISHELLS_DIR_NAME)).unwrap();
+
+        let problems = check_cache_leftovers(&storage).unwrap();
+        let _ = fs::remove_dir_all(&storage.root);
+
+        assert_eq!(problems.len(), 2);
+        assert!(problems[0].message.ends_with("2.json."));
+        assert!(problems[1].message.ends_with("3.28.1."));
+    }
+
+    #[test]
+    fn test_check_installed_reports_non_executable_cmake() {
+        let storage = storage_in("cmvm_test_doctor_installed");
+        install_fake(&storage, "3.20.0", 0o755);
+        install_fake(&storage, "3.28.1", 0o644);
+
+        let problems = check_installed(&storage).unwrap();
+        let _ = fs::remove_dir_all(&storage.root);
+
+        assert_eq!(problems.len(), 1);
+        assert!(problems[0].message.contains("3.28.1"));
+    }
+}
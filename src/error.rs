@@ -0,0 +1,113 @@
+use std::fmt;
+use std::process::ExitCode;
+
+/// Failures that scripts and CI are expected to tell apart. Each one maps to
+/// its own process exit code; any other error exits with 1.
+#[derive(Debug, Clone, PartialEq)]
+pub enum CmvmError {
+    VersionNotFound(String),
+    NotInstalled(String),
+    Network(String),
+    UnsupportedPlatform,
+    InstallFailed {
+        version: String,
+        reason: String,
+    },
+    /// Offline, the version is missing from `releases.json` or its archive
+    /// was never kept.
+    NotInOfflineCache(String),
+    /// `cmvm doctor` found this many problems.
+    ProblemsFound(usize),
+}
+
+impl CmvmError {
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            CmvmError::VersionNotFound(_) => 3,
+            CmvmError::NotInstalled(_) => 4,
+            CmvmError::Network(_) => 5,
+            CmvmError::UnsupportedPlatform => 6,
+            CmvmError::InstallFailed { .. } => 7,
+            CmvmError::NotInOfflineCache(_) => 8,
+            CmvmError::ProblemsFound(_) => 9,
+        }
+    }
+}
+
+impl fmt::Display for CmvmError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CmvmError::VersionNotFound(version) => {
+                write!(f, "[cmvm] Version {} not found.", version)
+            }
+            CmvmError::NotInstalled(version) => write!(
+                f,
+                "[cmvm] Version {} is not installed. Use `cmvm install {}` first.",
+                version, version
+            ),
+            CmvmError::Network(reason) => write!(f, "[cmvm] Network error: {}", reason),
+            CmvmError::UnsupportedPlatform => write!(f, "[cmvm] Platform not supported."),
+            CmvmError::InstallFailed { version, reason } => write!(
+                f,
+                "[cmvm] Error while installing version {}: {}",
+                version, reason
+            ),
+            CmvmError::NotInOfflineCache(version) => write!(
+                f,
+                "[cmvm] Version {} not in offline cache. Run without --offline to download it.",
+                version
+            ),
+            CmvmError::ProblemsFound(count) => write!(f, "[cmvm] {} problem(s) found.", count),
+        }
+    }
+}
+
+impl std::error::Error for CmvmError {}
+
+/// Returns the exit code for `error`: the code of the first `CmvmError` in
+/// its chain, or 1 for anything else.
+pub fn exit_code(error: &anyhow::Error) -> ExitCode {
+    let code = error
+        .chain()
+        .find_map(|cause| cause.downcast_ref::<CmvmError>())
+        .map_or(1, CmvmError::exit_code);
+    ExitCode::from(code)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Context;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_exit_codes_are_distinct() {
+        let errors = [
+            CmvmError::VersionNotFound("9.9.9".to_string()),
+            CmvmError::NotInstalled("3.28.1".to_string()),
+            CmvmError::Network("timeout".to_string()),
+            CmvmError::UnsupportedPlatform,
+            CmvmError::InstallFailed {
+                version: "3.28.1".to_string(),
+                reason: "disk full".to_string(),
+            },
+            CmvmError::NotInOfflineCache("3.28.1".to_string()),
+            CmvmError::ProblemsFound(2),
+        ];
+        let mut codes: Vec<u8> = errors.iter().map(CmvmError::exit_code).collect();
+        codes.sort();
+        codes.dedup();
+
+        assert_eq!(codes, vec![3, 4, 5, 6, 7, 8, 9]);
+    }
+
+    #[test]
+    fn test_exit_code_looks_through_context() {
+        let error = Err::<(), _>(CmvmError::VersionNotFound("9.9.9".to_string()))
+            .context("while syncing")
+            .unwrap_err();
+
+        assert_eq!(exit_code(&error), ExitCode::from(3));
+        assert_eq!(exit_code(&anyhow::anyhow!("other")), ExitCode::from(1));
+    }
+}
@@ -1,13 +1,33 @@
+use crate::error::CmvmError;
 use anyhow::Result;
-use reqwest::blocking::Client;
+use reqwest::blocking::{Client, ClientBuilder};
+use std::time::Duration;
 
 pub type Response = reqwest::blocking::Response;
 
-pub fn get(url: &str) -> Result<Response> {
-    Client::new()
+/// GETs an API `url`. `timeout` bounds the whole request; without it
+/// reqwest's default applies.
+pub fn get(url: &str, timeout: Option<Duration>) -> Result<Response> {
+    let mut client = Client::builder();
+    if let Some(timeout) = timeout {
+        client = client.timeout(timeout);
+    }
+    send(client, url)
+}
+
+/// GETs an archive to download. `timeout` only bounds connecting, so a
+/// large archive on a slow link is not cut off halfway.
+pub fn download(url: &str, timeout: Option<Duration>) -> Result<Response> {
+    send(Client::builder().connect_timeout(timeout), url)
+}
+
+fn send(client: ClientBuilder, url: &str) -> Result<Response> {
+    client
+        .build()
+        .map_err(|e| CmvmError::Network(e.to_string()))?
         .get(url)
         .header("User-Agent", concat!("cmvm ", env!("CARGO_PKG_VERSION")))
         .header("Accept", "application/vnd.github.v3+json")
         .send()
-        .map_err(Into::into)
+        .map_err(|e| CmvmError::Network(e.to_string()).into())
 }
@@ -1,17 +1,41 @@
-use clap::{Parser, Subcommand};
+use clap::error::ErrorKind;
+use clap::{CommandFactory, Parser, Subcommand};
+use clap_complete::{ArgValueCandidates, CompleteEnv};
 
+mod aliases;
 mod cache;
 mod commands;
+mod completions;
+mod config;
 mod constants;
+mod detect;
+mod doctor;
+mod error;
 mod http;
+mod manifest;
+mod multishell;
+mod output;
 mod package;
 mod platform;
+mod project;
+mod prune;
 mod releases;
+mod resolver;
+mod shell;
+mod shims;
+mod source;
 mod storage;
+mod upgrade;
 mod versions;
 
-use crate::storage::StorageImpl;
-use anyhow::Result;
+use crate::config::Config;
+use crate::constants::{COMPLETE_ENV_VAR, MANIFEST_FILE_NAME};
+use anyhow::{bail, Result};
+use std::env;
+use std::ffi::OsString;
+use std::fs;
+use std::path::PathBuf;
+use std::process::ExitCode;
 
 #[derive(Parser)]
 #[clap(version, about = "cmake version manager")]
@@ -19,45 +43,528 @@ use anyhow::Result;
 struct Cli {
     #[clap(subcommand)]
     command: CliCommands,
+
+    /// Output format. Commands that print scripts, paths or run another program only print text [default: text]
+    #[clap(long, global = true, value_enum)]
+    output: Option<output::OutputFormat>,
+
+    /// Keep versions, `current` and the cache under DIR [env: CMVM_HOME]
+    #[clap(long, global = true, value_name = "DIR")]
+    root: Option<PathBuf>,
+
+    /// Cache directory, overriding the one under --root [env: CMVM_CACHE_DIR]
+    #[clap(long, global = true, value_name = "DIR")]
+    cache_dir: Option<PathBuf>,
+
+    /// Directory of installed versions, overriding the one under --root [env: CMVM_VERSIONS_DIR]
+    #[clap(long, global = true, value_name = "DIR")]
+    versions_dir: Option<PathBuf>,
+
+    /// Never touch the network: use the cached release index and kept archives [env: CMVM_OFFLINE]
+    #[clap(long, global = true)]
+    offline: bool,
 }
 
 #[derive(Subcommand)]
 enum CliCommands {
-    /// Install a cmake version
-    Install { v: String },
+    /// Install a cmake version (e.g. `3.28.1`, `3.28`, `latest`, `>=3.20,<3.25`, `~3.22`, `^3`)
+    ///
+    /// Reads the closest `.cmake-version` file when no version is given
+    Install {
+        #[clap(conflicts_with_all = ["from_project", "local_install"], add = ArgValueCandidates::new(completions::remote_candidates))]
+        v: Option<String>,
+
+        /// Pick the version from `cmake_minimum_required` in ./CMakeLists.txt
+        #[clap(long)]
+        from_project: bool,
+
+        /// Let `latest`, partial versions and ranges resolve to release candidates too
+        #[clap(long)]
+        pre: bool,
+
+        /// Install a downloaded cmake .tar.gz archive instead of fetching one
+        #[clap(
+            long,
+            value_name = "ARCHIVE",
+            group = "local_install",
+            conflicts_with = "from_project"
+        )]
+        from_file: Option<PathBuf>,
+
+        /// Build cmake from a source directory, source tarball, release version or git ref
+        #[clap(
+            long,
+            value_name = "SOURCE",
+            group = "local_install",
+            conflicts_with = "from_project"
+        )]
+        from_source: Option<String>,
+
+        /// Name to install the archive or build under instead of its cmake version
+        #[clap(long = "as", value_name = "NAME", requires = "local_install")]
+        name: Option<String>,
+
+        /// Number of parallel build jobs for --from-source [default: number of CPUs]
+        #[clap(short, long, requires = "from_source")]
+        jobs: Option<usize>,
+
+        /// Extra argument for cmake's bootstrap script, e.g. `--bootstrap-arg=--no-qt-gui`
+        #[clap(
+            long,
+            value_name = "ARG",
+            allow_hyphen_values = true,
+            requires = "from_source"
+        )]
+        bootstrap_arg: Vec<String>,
+
+        /// Install into the shared system store (e.g. /opt/cmvm/versions) for all users
+        #[clap(long)]
+        system: bool,
+    },
 
     /// Uninstall a cmake version
-    Uninstall { v: String },
+    Uninstall {
+        #[clap(add = ArgValueCandidates::new(completions::installed_candidates))]
+        v: String,
+
+        /// Remove the version from the shared system store
+        #[clap(long)]
+        system: bool,
+    },
 
     /// Use a cmake version
-    Use { v: String },
+    ///
+    /// Reads the closest `.cmake-version` file when no version is given
+    Use {
+        #[clap(add = ArgValueCandidates::new(completions::installed_candidates))]
+        v: Option<String>,
+    },
+
+    /// Run a command with a cmake version without switching to it
+    Exec {
+        #[clap(add = ArgValueCandidates::new(completions::installed_candidates))]
+        v: String,
+
+        /// Install the version first when it is not installed
+        #[clap(long)]
+        install: bool,
+
+        /// Command to run, e.g. `cmvm exec 3.20 -- cmake -B build`
+        #[clap(last = true, required = true)]
+        command: Vec<String>,
+    },
+
+    /// Point a named alias at an installed cmake version
+    Alias { name: String, v: String },
+
+    /// Remove a named alias
+    Unalias { name: String },
+
+    /// List all aliases
+    Aliases,
+
+    /// Show release date, notes, download size and install state of a version
+    Info {
+        #[clap(add = ArgValueCandidates::new(completions::remote_candidates))]
+        v: String,
+    },
+
+    /// Show installed versions with newer patch or minor releases
+    Outdated,
+
+    /// Install the newest patch release of installed versions
+    ///
+    /// Upgrades every installed version when none is given. `current` and
+    /// aliases follow the upgraded version.
+    Upgrade {
+        #[clap(add = ArgValueCandidates::new(completions::installed_candidates))]
+        v: Option<String>,
+
+        /// Upgrade to the newest minor release of the same major version
+        #[clap(long)]
+        minor: bool,
+
+        /// Uninstall the superseded version afterwards
+        #[clap(long)]
+        remove_old: bool,
+    },
+
+    /// Remove installed versions that are no longer needed
+    ///
+    /// A version is removed when none of the given policies keeps it. The active
+    /// version and aliased versions are always kept.
+    Prune {
+        /// Keep the N newest versions
+        #[clap(long, value_name = "N")]
+        keep: Option<usize>,
+
+        /// Keep the latest patch release of every minor version
+        #[clap(long)]
+        keep_latest_patch: bool,
+
+        /// Keep versions used within the last DAYS days
+        #[clap(long, value_name = "DAYS")]
+        unused_days: Option<u64>,
+
+        /// Show what would be removed and how much space would be reclaimed
+        #[clap(long)]
+        dry_run: bool,
+    },
+
+    /// Install the versions, aliases and default listed in a manifest
+    Sync {
+        #[clap(long, default_value = MANIFEST_FILE_NAME)]
+        file: PathBuf,
+
+        /// Remove installed versions that are not in the manifest
+        #[clap(long)]
+        remove_unlisted: bool,
+    },
+
+    /// Write the installed versions, aliases and default to a manifest
+    Freeze {
+        #[clap(long, default_value = MANIFEST_FILE_NAME)]
+        file: PathBuf,
+    },
 
     /// List all cmake versions installed
     List,
 
     /// List available cmake versions to install
-    ListRemote,
+    ///
+    /// Versions are grouped by minor series; installed ones are marked with `*`.
+    ListRemote {
+        /// Also show prereleases, old major versions and releases without an asset for this platform
+        #[clap(long)]
+        all: bool,
+
+        /// Also show release candidates
+        #[clap(long)]
+        prerelease: bool,
+
+        /// Only show versions with this major version
+        #[clap(long)]
+        major: Option<i32>,
+
+        /// Only show versions with this minor version
+        #[clap(long, requires = "major")]
+        minor: Option<i32>,
+
+        /// Only show the newest release of each minor series
+        #[clap(long)]
+        latest_per_minor: bool,
+
+        /// Only show releases published on or after this date (YYYY-MM-DD)
+        #[clap(long, value_parser = versions::parse_date)]
+        since: Option<String>,
+
+        /// Only show installed versions
+        #[clap(long, conflicts_with = "not_installed")]
+        installed: bool,
+
+        /// Only show versions that are not installed
+        #[clap(long)]
+        not_installed: bool,
+    },
 
     /// Show the currently active cmake version
     Current,
 
+    /// Detect the cmake version required by ./CMakeLists.txt
+    Detect,
+
     /// Show how to put cmake current version on PATH env variable
     Shell,
+
+    /// Print a hook that switches cmake on PATH when changing directories
+    Env {
+        #[clap(long, value_enum)]
+        shell: shell::Shell,
+
+        /// Give this shell its own `current` link so `cmvm use` does not affect other terminals
+        #[clap(long)]
+        multishell: bool,
+    },
+
+    /// Print the shell completion script, e.g. `source <(cmvm completions bash)`
+    Completions {
+        #[clap(value_enum)]
+        shell: completions::CompletionShell,
+    },
+
+    /// Show or change settings in config.toml
+    Config {
+        #[clap(subcommand)]
+        command: ConfigCommands,
+    },
+
+    /// Print the cmake bin directory for the current directory
+    #[clap(hide = true)]
+    BinPath,
+
+    /// Check the cmvm setup for common problems
+    Doctor,
+
+    /// Regenerate the cmake, ctest, cpack, ccmake and cmake-gui shims
+    Rehash,
+}
+
+impl CliCommands {
+    /// Whether `--output json` prints a JSON document. The other commands
+    /// print shell code, a path or a file, or hand over to another program.
+    fn has_json_output(&self) -> bool {
+        !matches!(
+            self,
+            CliCommands::Exec { .. }
+                | CliCommands::Alias { .. }
+                | CliCommands::Unalias { .. }
+                | CliCommands::Freeze { .. }
+                | CliCommands::Shell
+                | CliCommands::Env { .. }
+                | CliCommands::Completions { .. }
+                | CliCommands::BinPath
+                | CliCommands::Rehash
+                | CliCommands::Config {
+                    command: ConfigCommands::Get { .. }
+                        | ConfigCommands::Set { .. }
+                        | ConfigCommands::Unset { .. }
+                }
+        )
+    }
+}
+
+#[derive(Subcommand)]
+enum ConfigCommands {
+    /// Print the effective value of a setting
+    Get { key: String },
+
+    /// Write a setting to the user config file
+    Set {
+        key: String,
+        value: String,
+
+        /// Write to the system config file instead
+        #[clap(long)]
+        system: bool,
+    },
+
+    /// Remove a setting from the user config file
+    Unset {
+        key: String,
+
+        /// Remove it from the system config file instead
+        #[clap(long)]
+        system: bool,
+    },
+
+    /// List the effective settings from config files, environment and flags
+    List,
 }
 
-fn main() -> Result<()> {
-    let storage = StorageImpl::default();
+fn main() -> ExitCode {
+    CompleteEnv::with_factory(Cli::command)
+        .var(COMPLETE_ENV_VAR)
+        .complete();
 
+    let args: Vec<OsString> = env::args_os().collect();
+    let result = match args.get(1) {
+        Some(command) if command == "shim" => run_shim(&args[2..]),
+        _ => run(),
+    };
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("{:#}", e);
+            error::exit_code(&e)
+        }
+    }
+}
+
+/// `cmvm shim <name> [args...]`, run by the generated shims. It skips clap
+/// so every argument after the tool name reaches the tool unchanged, even
+/// ones that look like cmvm flags such as `--version` or `--offline`.
+fn run_shim(args: &[OsString]) -> Result<()> {
+    let Some(name) = args.first().and_then(|name| name.to_str()) else {
+        bail!("[cmvm] Usage: cmvm shim <name> [args...]");
+    };
+    let storage = storage::from_config(&Config::load()?)?;
     cache::bootstrap(&storage)?;
+    commands::run_shim(name, &args[1..], &storage)
+}
+
+/// The settings from the config files and environment, with the global
+/// flags applied on top.
+fn load_config(cli: &Cli) -> Result<Config> {
+    let mut config = Config::load()?;
+    if cli.offline {
+        config.offline = Some(true);
+    }
+    config.output = cli.output.or(config.output);
+    config.root = cli.root.clone().or(config.root);
+    config.cache_dir = cli.cache_dir.clone().or(config.cache_dir);
+    config.versions_dir = cli.versions_dir.clone().or(config.versions_dir);
+    Ok(config)
+}
+
+/// `cmvm config` runs without a storage, so a broken setting can still be
+/// fixed with it. `set` and `unset` only touch the file they edit.
+fn run_config(command: &ConfigCommands, cli: &Cli) -> Result<()> {
+    match command {
+        ConfigCommands::Get { key } => commands::get_setting(key, &load_config(cli)?),
+        ConfigCommands::Set { key, value, system } => commands::set_setting(key, value, *system),
+        ConfigCommands::Unset { key, system } => commands::unset_setting(key, *system),
+        ConfigCommands::List => {
+            let config = load_config(cli)?;
+            commands::list_settings(&config, config.output.unwrap_or_default())
+        }
+    }
+}
+
+fn run() -> Result<()> {
+    let cli = Cli::parse();
+    // an `output = "json"` default from config.toml leaves these on text
+    if cli.output == Some(output::OutputFormat::Json) && !cli.command.has_json_output() {
+        Cli::command()
+            .error(
+                ErrorKind::ArgumentConflict,
+                "this command has no JSON output, run it without `--output json`",
+            )
+            .exit();
+    }
+    if let CliCommands::Config { command } = &cli.command {
+        return run_config(command, &cli);
+    }
+    let mut config = load_config(&cli)?;
+
+    // `--system` installs and uninstalls work on the system store instead
+    // of the user's `versions/`
+    if let CliCommands::Install { system: true, .. } | CliCommands::Uninstall { system: true, .. } =
+        cli.command
+    {
+        let system_versions_dir = config.system_versions_dir();
+        storage::ensure_writable(&system_versions_dir)?;
+        fs::create_dir_all(&system_versions_dir)?;
+        config.versions_dir = Some(system_versions_dir);
+    }
+
+    let storage = storage::from_config(&config)?;
+    cache::bootstrap(&storage)?;
+
+    let format = config.output.unwrap_or_default();
 
-    match Cli::parse().command {
-        CliCommands::Install { v } => commands::install_version(&v, &storage)?,
-        CliCommands::Uninstall { v } => commands::uninstall_version(&v, &storage)?,
-        CliCommands::Use { v } => commands::use_version(&v, &storage)?,
-        CliCommands::List => commands::list_versions(&storage)?,
-        CliCommands::ListRemote => commands::list_remote_versions(&storage)?,
-        CliCommands::Current => commands::current_version(&storage)?,
+    match cli.command {
+        CliCommands::Install {
+            from_file: Some(archive),
+            name,
+            ..
+        } => commands::install_from_file(&archive, name.as_deref(), format, &storage)?,
+        CliCommands::Install {
+            from_source: Some(source),
+            name,
+            jobs,
+            bootstrap_arg,
+            ..
+        } => {
+            let options = source::BuildOptions {
+                jobs: jobs
+                    .unwrap_or_else(|| std::thread::available_parallelism().map_or(1, |n| n.get())),
+                bootstrap_args: bootstrap_arg,
+            };
+            commands::install_from_source(
+                &source,
+                name.as_deref(),
+                &options,
+                format,
+                &config,
+                &storage,
+            )?
+        }
+        CliCommands::Install { from_project, .. } if from_project => {
+            commands::install_from_project(format, &config, &storage)?
+        }
+        CliCommands::Install { v, pre, .. } => commands::install_version(
+            &commands::get_version_argument(v)?,
+            pre || config.prerelease(),
+            format,
+            &config,
+            &storage,
+        )?,
+        CliCommands::Uninstall { v, .. } => commands::uninstall_version(&v, format, &storage)?,
+        CliCommands::Use { v } => {
+            commands::use_version(&commands::get_version_argument(v)?, format, &storage)?
+        }
+        CliCommands::Exec {
+            v,
+            install,
+            command,
+        } => commands::exec_version(&v, install, &command, &config, &storage)?,
+        CliCommands::Alias { name, v } => commands::set_alias(&name, &v, &storage)?,
+        CliCommands::Unalias { name } => commands::remove_alias(&name, &storage)?,
+        CliCommands::Aliases => commands::list_aliases(format, &storage)?,
+        CliCommands::Info { v } => commands::show_version_info(&v, format, &config, &storage)?,
+        CliCommands::Outdated => commands::list_outdated(format, &config, &storage)?,
+        CliCommands::Upgrade {
+            v,
+            minor,
+            remove_old,
+        } => {
+            commands::upgrade_versions(v.as_deref(), minor, remove_old, format, &config, &storage)?
+        }
+        CliCommands::Prune {
+            keep,
+            keep_latest_patch,
+            unused_days,
+            dry_run,
+        } => {
+            let policy = prune::PrunePolicy {
+                keep_newest: keep,
+                keep_latest_patch,
+                unused_days,
+            };
+            commands::prune_versions(&policy, dry_run, format, &storage)?
+        }
+        CliCommands::Sync {
+            file,
+            remove_unlisted,
+        } => commands::sync_versions(&file, remove_unlisted, format, &config, &storage)?,
+        CliCommands::Freeze { file } => commands::freeze_versions(&file, &storage)?,
+        CliCommands::List => commands::list_versions(format, &storage)?,
+        CliCommands::ListRemote {
+            all,
+            prerelease,
+            major,
+            minor,
+            latest_per_minor,
+            since,
+            installed,
+            not_installed,
+        } => {
+            let filter = versions::RemoteFilter {
+                all,
+                prerelease: prerelease || config.prerelease(),
+                major,
+                minor,
+                latest_per_minor,
+                since,
+                installed: match (installed, not_installed) {
+                    (true, _) => Some(true),
+                    (_, true) => Some(false),
+                    _ => None,
+                },
+            };
+            commands::list_remote_versions(&filter, format, &config, &storage)?
+        }
+        CliCommands::Current => commands::current_version(format, &storage)?,
+        CliCommands::Detect => commands::detect_version(format, &config, &storage)?,
         CliCommands::Shell => commands::display_shell_instructions(&storage)?,
+        CliCommands::Env { shell, multishell } => {
+            commands::display_shell_hook(shell, multishell, &storage)?
+        }
+        CliCommands::Completions { shell } => commands::display_completions(shell)?,
+        CliCommands::Config { .. } => unreachable!("handled before the storage is set up"),
+        CliCommands::BinPath => commands::display_bin_path(&storage)?,
+        CliCommands::Doctor => commands::run_doctor(format, &storage)?,
+        CliCommands::Rehash => commands::rehash_shims(&storage)?,
     }
     Ok(())
 }
@@ -0,0 +1,265 @@
+use crate::config::Config;
+use crate::error::CmvmError;
+use crate::storage::{Storage, Store};
+use crate::versions::Version;
+use crate::{aliases, cache, package, platform, releases, resolver};
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::path::Path;
+
+/// The set of cmake versions a machine should have, as read by `cmvm sync`
+/// and written by `cmvm freeze`:
+///
+/// ```toml
+/// default = "3.28.1"
+/// versions = ["3.20.6", "3.28.1"]
+///
+/// [aliases]
+/// legacy = "3.20.6"
+/// ```
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct Manifest {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default: Option<String>,
+    #[serde(default)]
+    pub versions: Vec<String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub aliases: BTreeMap<String, String>,
+}
+
+impl Manifest {
+    pub fn load(path: &Path) -> Result<Manifest> {
+        if !path.exists() {
+            bail!("[cmvm] Manifest {} not found.", path.display());
+        }
+        Ok(toml::from_str(&cache::open_file(path.to_path_buf())?)?)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let mut file = cache::create_file(path)?;
+        file.write_all(toml::to_string(self)?.as_bytes())?;
+        Ok(())
+    }
+
+    /// Describes the installed versions, aliases and default version.
+    pub fn freeze(storage: &impl Storage) -> Result<Manifest> {
+        let default = storage
+            .get_current_version_dir()?
+            .read_link()
+            .ok()
+            .and_then(|target| target.file_name().map(|n| n.to_string_lossy().to_string()));
+
+        Ok(Manifest {
+            default,
+            versions: Version::all_installed(storage)?
+                .iter()
+                .map(Version::get_tag_name)
+                .collect(),
+            aliases: aliases::all(storage)?,
+        })
+    }
+
+    /// The listed versions that are not installed under that exact name and
+    /// so have to be looked up in the release index.
+    pub fn missing(&self, storage: &impl Storage) -> Result<Vec<&str>> {
+        let mut missing = Vec::new();
+        for request in &self.versions {
+            if storage.find_version_dir(request)?.is_none() {
+                missing.push(request.as_str());
+            }
+        }
+        Ok(missing)
+    }
+}
+
+/// What `sync` changed.
+#[derive(Serialize, Debug, Clone, Default, PartialEq)]
+pub struct SyncReport {
+    pub installed: Vec<String>,
+    pub already_installed: Vec<String>,
+    pub removed: Vec<String>,
+    /// Aliases created or moved, with the version they now point at.
+    pub aliases: BTreeMap<String, String>,
+    /// The new default version, when it changed.
+    pub default: Option<String>,
+}
+
+/// Brings `versions/` in line with the manifest: installs missing versions,
+/// optionally removes unlisted ones, then sets aliases and the default.
+/// Running it twice in a row does nothing the second time. Entries naming an
+/// installed directory, like the `--as` and `--from-source` names written by
+/// `freeze`, are used as they are; the others are resolved against the cached
+/// release index, so refresh it beforehand when `Manifest::missing` is not
+/// empty.
+pub fn sync(
+    manifest: &Manifest,
+    remove_unlisted: bool,
+    config: &Config,
+    storage: &impl Storage,
+) -> Result<SyncReport> {
+    let mut report = SyncReport::default();
+    let mut wanted = Vec::new();
+
+    for request in &manifest.versions {
+        if storage.find_version_dir(request)?.is_some() {
+            report.already_installed.push(request.clone());
+            wanted.push(request.clone());
+            continue;
+        }
+
+        let Some(version) = resolver::resolve_remote(request, storage)? else {
+            bail!(releases::version_not_found(request, config));
+        };
+        let tag_name = version.get_tag_name();
+
+        if storage.find_version_dir(&tag_name)?.is_some() {
+            report.already_installed.push(tag_name.clone());
+        } else {
+            if !platform::is_supported_platform() {
+                bail!(CmvmError::UnsupportedPlatform);
+            }
+            package::get_cmake_release(&version, config, storage)?;
+            report.installed.push(tag_name.clone());
+        }
+        wanted.push(tag_name);
+    }
+
+    if remove_unlisted {
+        for version in Version::all_installed_in(Store::User, storage)? {
+            let tag_name = version.get_tag_name();
+            if !wanted.contains(&tag_name) {
+                for alias in aliases::for_version(&tag_name, storage)? {
+                    aliases::remove(&alias, storage)?;
+                }
+                releases::delete_cache_release(&tag_name, storage)?;
+                report.removed.push(tag_name);
+            }
+        }
+    }
+
+    for (name, request) in &manifest.aliases {
+        let Some(version) = resolver::resolve_installed(request, storage)? else {
+            bail!(
+                "[cmvm] Alias {} points at {}, which is not installed.",
+                name,
+                request
+            );
+        };
+        if aliases::get(name, storage)? != Some(version.get_tag_name()) {
+            aliases::set(name, &version.get_tag_name(), storage)?;
+            report
+                .aliases
+                .insert(name.to_string(), version.get_tag_name());
+        }
+    }
+
+    if let Some(request) = &manifest.default {
+        let Some(mut version) = resolver::resolve_installed(request, storage)? else {
+            bail!(CmvmError::NotInstalled(request.to_string()));
+        };
+        let version_path = storage
+            .find_version_dir(&version.get_tag_name())?
+            .map(|(path, _)| path);
+        if storage.get_current_version_dir()?.read_link().ok() != version_path {
+            version.r#use(storage)?;
+            report.default = Some(version.get_tag_name());
+        }
+    }
+
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::RELEASES_FILE_NAME;
+    use crate::storage::test_support::MockStorage;
+    use pretty_assertions::assert_eq;
+    use std::env;
+    use std::fs;
+
+    fn setup(name: &str, installed: &[&str]) -> MockStorage {
+        let cache_dir = env::temp_dir().join(name);
+        let _ = fs::remove_dir_all(&cache_dir);
+        for tag in installed {
+            fs::create_dir_all(cache_dir.join("versions").join(tag)).unwrap();
+        }
+        MockStorage::new(&cache_dir)
+    }
+
+    #[test]
+    fn test_manifest_round_trip() {
+        let contents = "default = \"3.28.1\"\nversions = [\"3.20.6\", \"3.28.1\"]\n\n[aliases]\nlegacy = \"3.20.6\"\n";
+        let manifest: Manifest = toml::from_str(contents).unwrap();
+
+        assert_eq!(manifest.default, Some("3.28.1".to_string()));
+        assert_eq!(manifest.versions, vec!["3.20.6", "3.28.1"]);
+        assert_eq!(manifest.aliases.get("legacy"), Some(&"3.20.6".to_string()));
+        assert_eq!(toml::to_string(&manifest).unwrap(), contents);
+    }
+
+    #[test]
+    fn test_freeze_describes_installed_state() {
+        let storage = setup("cmvm_test_manifest_freeze", &["3.20.6", "3.28.1"]);
+        let versions_dir = storage.cache_dir.join("versions");
+        std::os::unix::fs::symlink(
+            versions_dir.join("3.28.1"),
+            storage.cache_dir.join("current"),
+        )
+        .unwrap();
+        aliases::set("legacy", "3.20.6", &storage).unwrap();
+
+        let manifest = Manifest::freeze(&storage).unwrap();
+        let _ = fs::remove_dir_all(&storage.cache_dir);
+
+        assert_eq!(manifest.default, Some("3.28.1".to_string()));
+        assert_eq!(manifest.versions, vec!["3.20.6", "3.28.1"]);
+        assert_eq!(manifest.aliases.get("legacy"), Some(&"3.20.6".to_string()));
+    }
+
+    #[test]
+    fn test_sync_applies_aliases_default_and_removes_unlisted() {
+        let storage = setup("cmvm_test_manifest_sync", &["3.18.0", "3.20.6", "3.28.1"]);
+        fs::write(
+            storage.cache_dir.join(RELEASES_FILE_NAME),
+            r#"[{"tag_name": "v3.20.6", "assets": []}, {"tag_name": "v3.28.1", "assets": []}]"#,
+        )
+        .unwrap();
+        let manifest = Manifest {
+            default: Some("3.28.1".to_string()),
+            versions: vec!["3.20.6".to_string(), "3.28.1".to_string()],
+            aliases: BTreeMap::from([("legacy".to_string(), "3.20".to_string())]),
+        };
+
+        sync(&manifest, true, &Config::default(), &storage).unwrap();
+        sync(&manifest, true, &Config::default(), &storage).unwrap();
+        let frozen = Manifest::freeze(&storage).unwrap();
+        let _ = fs::remove_dir_all(&storage.cache_dir);
+
+        assert_eq!(frozen.versions, vec!["3.20.6", "3.28.1"]);
+        assert_eq!(frozen.default, Some("3.28.1".to_string()));
+        assert_eq!(frozen.aliases.get("legacy"), Some(&"3.20.6".to_string()));
+    }
+
+    #[test]
+    fn test_sync_keeps_installed_names_without_release_index() {
+        let storage = setup("cmvm_test_manifest_sync_frozen", &["3.28.1", "patched"]);
+        let manifest = Manifest {
+            default: Some("patched".to_string()),
+            versions: vec!["3.28.1".to_string(), "patched".to_string()],
+            aliases: BTreeMap::new(),
+        };
+
+        let missing = manifest.missing(&storage).unwrap().len();
+        let result = sync(&manifest, true, &Config::default(), &storage);
+        let frozen = Manifest::freeze(&storage).unwrap();
+        let _ = fs::remove_dir_all(&storage.cache_dir);
+
+        assert_eq!(missing, 0);
+        assert!(result.is_ok());
+        assert_eq!(frozen.versions, vec!["patched", "3.28.1"]);
+        assert_eq!(frozen.default, Some("patched".to_string()));
+    }
+}
@@ -0,0 +1,131 @@
+use crate::cache;
+use crate::constants::MULTISHELL_ENV_VAR;
+use crate::storage::Storage;
+use anyhow::Result;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub fn is_active() -> bool {
+    env::var_os(MULTISHELL_ENV_VAR).is_some_and(|p| !p.is_empty())
+}
+
+/// Creates a `current` symlink private to the calling shell. The link is named
+/// after the shell pid so it can be collected once the shell exits, and it
+/// starts out pointing at the version active in the calling environment.
+pub fn create_session_link(storage: &impl Storage) -> Result<PathBuf> {
+    let multishells_dir = storage.get_multishells_dir()?;
+    fs::create_dir_all(&multishells_dir)?;
+    clean_stale_links(storage)?;
+
+    let timestamp = SystemTime::now().duration_since(UNIX_EPOCH)?.as_millis();
+    let session_link = multishells_dir.join(format!(
+        "{}_{}",
+        std::os::unix::process::parent_id(),
+        timestamp
+    ));
+
+    if let Ok(target) = storage.get_current_version_dir()?.read_link() {
+        std::os::unix::fs::symlink(target, &session_link)?;
+    }
+
+    Ok(session_link)
+}
+
+/// Removes session links whose shell is no longer running and returns how
+/// many were removed.
+pub fn clean_stale_links(storage: &impl Storage) -> Result<usize> {
+    let multishells_dir = storage.get_multishells_dir()?;
+    if !multishells_dir.exists() {
+        return Ok(0);
+    }
+
+    let mut removed = 0;
+    for link in cache::ls(&multishells_dir)? {
+        if is_stale(&link) {
+            cache::delete(&link)?;
+            removed += 1;
+        }
+    }
+    Ok(removed)
+}
+
+/// The session links of shells that are still running.
+pub fn live_links(storage: &impl Storage) -> Result<Vec<PathBuf>> {
+    let multishells_dir = storage.get_multishells_dir()?;
+    if !multishells_dir.exists() {
+        return Ok(vec![]);
+    }
+    Ok(cache::ls(&multishells_dir)?
+        .into_iter()
+        .filter(|link| !is_stale(link))
+        .collect())
+}
+
+fn is_stale(link: &Path) -> bool {
+    let pid = link
+        .file_name()
+        .and_then(|name| name.to_str())
+        .and_then(|name| name.split('_').next())
+        .and_then(|pid| pid.parse::<i32>().ok());
+
+    match pid {
+        Some(pid) => !is_process_alive(pid),
+        None => false,
+    }
+}
+
+fn is_process_alive(pid: i32) -> bool {
+    // signal 0 only checks whether the process exists; EPERM means it exists
+    // but belongs to another user
+    let result = unsafe { libc::kill(pid, 0) };
+    result == 0 || std::io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::test_support::MockStorage;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_create_session_link_points_at_current_version() {
+        let cache_dir = env::temp_dir().join("cmvm_test_multishell_create");
+        let _ = fs::remove_dir_all(&cache_dir);
+        let version_dir = cache_dir.join("versions").join("3.28.1");
+        fs::create_dir_all(&version_dir).unwrap();
+        std::os::unix::fs::symlink(&version_dir, cache_dir.join("current")).unwrap();
+        let storage = MockStorage::new(&cache_dir);
+
+        let session_link = create_session_link(&storage).unwrap();
+        let target = session_link.read_link().unwrap();
+        let parent = session_link.parent().map(Path::to_path_buf);
+        let _ = fs::remove_dir_all(&cache_dir);
+
+        assert_eq!(target, version_dir);
+        assert_eq!(parent, Some(cache_dir.join("multishells")));
+    }
+
+    #[test]
+    fn test_clean_stale_links_keeps_live_sessions() {
+        let cache_dir = env::temp_dir().join("cmvm_test_multishell_clean");
+        let _ = fs::remove_dir_all(&cache_dir);
+        let multishells_dir = cache_dir.join("multishells");
+        fs::create_dir_all(&multishells_dir).unwrap();
+        let live_link = multishells_dir.join(format!("{}_1", std::process::id()));
+        let stale_link = multishells_dir.join(format!("{}_1", i32::MAX));
+        std::os::unix::fs::symlink(&cache_dir, &live_link).unwrap();
+        std::os::unix::fs::symlink(&cache_dir, &stale_link).unwrap();
+        let storage = MockStorage::new(&cache_dir);
+
+        let removed = clean_stale_links(&storage).unwrap();
+        let live_exists = live_link.symlink_metadata().is_ok();
+        let stale_exists = stale_link.symlink_metadata().is_ok();
+        let _ = fs::remove_dir_all(&cache_dir);
+
+        assert_eq!(removed, 1);
+        assert!(live_exists);
+        assert!(!stale_exists);
+    }
+}
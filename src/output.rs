@@ -0,0 +1,449 @@
+use crate::doctor::Problem;
+use crate::manifest::SyncReport;
+use crate::prune;
+use crate::storage::Store;
+use crate::upgrade::{Outdated, Upgrade};
+use crate::versions::{Asset, InstalledVersion, Version};
+use anyhow::Result;
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    #[default]
+    Text,
+    Json,
+}
+
+impl OutputFormat {
+    pub fn is_json(&self) -> bool {
+        *self == OutputFormat::Json
+    }
+}
+
+/// A release from the cached index, as reported by `cmvm list-remote`.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct RemoteVersion {
+    pub version: String,
+    pub installed: bool,
+    pub assets: Vec<Asset>,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct InstallReport {
+    pub version: String,
+    pub already_installed: bool,
+    pub active: bool,
+    pub path: PathBuf,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct UninstallReport {
+    pub version: String,
+    pub removed_aliases: Vec<String>,
+}
+
+/// A release with its metadata, as reported by `cmvm info`.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct VersionInfo {
+    pub version: String,
+    pub prerelease: bool,
+    pub published_at: Option<String>,
+    pub html_url: Option<String>,
+    /// The asset `cmvm install` would download on this platform.
+    pub asset: Option<Asset>,
+    pub installed: bool,
+    pub path: Option<PathBuf>,
+    pub aliases: Vec<String>,
+    pub notes: Option<String>,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct UseReport {
+    pub version: String,
+    pub path: PathBuf,
+    /// Set for this shell session only rather than as the default.
+    pub session: bool,
+}
+
+/// The version `cmvm detect` picked for ./CMakeLists.txt.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct DetectReport {
+    pub version: String,
+    pub installed: bool,
+    pub reason: String,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct PruneReport {
+    pub dry_run: bool,
+    /// Versions removed, or that would be with `dry_run`.
+    pub removed: Vec<PrunedVersion>,
+    /// Bytes reclaimed.
+    pub reclaimed: u64,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct PrunedVersion {
+    pub version: String,
+    pub size: u64,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct CurrentReport {
+    pub version: Option<String>,
+    pub requested: Option<String>,
+    pub source: Option<String>,
+    pub installed: bool,
+    pub path: Option<PathBuf>,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct DoctorReport {
+    pub ok: bool,
+    pub problems: Vec<Problem>,
+}
+
+pub fn print_json<T: Serialize>(value: &T) -> Result<()> {
+    println!("{}", serde_json::to_string_pretty(value)?);
+    Ok(())
+}
+
+pub fn format_installed_versions(versions: &[InstalledVersion]) -> String {
+    versions
+        .iter()
+        .map(|v| {
+            let checked = if v.active { "*" } else { " " };
+            let mut line = format!("[cmvm] {} {}", checked, v.version);
+            if !v.aliases.is_empty() {
+                line.push_str(&format!(" ({})", v.aliases.join(", ")));
+            }
+            if v.store == Store::System {
+                line.push_str(" [system]");
+            }
+            line
+        })
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+/// One row per minor series, e.g. `[cmvm] 3.28  3.28.0   3.28.1*  3.28.2`,
+/// with cells padded to the widest version so the columns line up.
+pub fn format_remote_versions(versions: &[RemoteVersion]) -> String {
+    let cell_width = versions.iter().map(|v| v.version.len()).max().unwrap_or(0) + 1;
+    let mut rows: Vec<(String, Vec<String>)> = Vec::new();
+
+    for remote in versions {
+        let version = Version::from_tag_name(&remote.version);
+        let series = format!(
+            "{}.{}",
+            version.major.unwrap_or(0),
+            version.minor.unwrap_or(0)
+        );
+        let marker = if remote.installed { "*" } else { "" };
+        let cell = format!("{:<cell_width$}", format!("{}{}", remote.version, marker));
+
+        match rows.last_mut() {
+            Some((last_series, cells)) if *last_series == series => cells.push(cell),
+            _ => rows.push((series, vec![cell])),
+        }
+    }
+
+    let series_width = rows.iter().map(|(s, _)| s.len()).max().unwrap_or(0);
+    rows.iter()
+        .map(|(series, cells)| {
+            format!("[cmvm] {:<series_width$}  {}", series, cells.join(" "))
+                .trim_end()
+                .to_string()
+        })
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+pub fn format_version_info(info: &VersionInfo) -> String {
+    let mut lines = vec![format!(
+        "[cmvm] CMake {}{}",
+        info.version,
+        if info.prerelease { " (prerelease)" } else { "" }
+    )];
+    if let Some(published_at) = &info.published_at {
+        // RFC 3339 timestamps start with the date
+        lines.push(format!(
+            "[cmvm] Released: {}",
+            published_at.get(..10).unwrap_or(published_at)
+        ));
+    }
+    if let Some(html_url) = &info.html_url {
+        lines.push(format!("[cmvm] Release page: {}", html_url));
+    }
+    lines.push(match &info.asset {
+        Some(asset) => format!(
+            "[cmvm] Asset: {} ({})",
+            asset.name,
+            prune::format_size(asset.size)
+        ),
+        None => "[cmvm] Asset: none for this platform".to_string(),
+    });
+    lines.push(match &info.path {
+        Some(path) if info.installed => format!("[cmvm] Installed: yes, in {}", path.display()),
+        _ => "[cmvm] Installed: no".to_string(),
+    });
+    if !info.aliases.is_empty() {
+        lines.push(format!("[cmvm] Aliases: {}", info.aliases.join(", ")));
+    }
+    if let Some(notes) = info
+        .notes
+        .as_deref()
+        .map(str::trim)
+        .filter(|n| !n.is_empty())
+    {
+        lines.push(String::new());
+        lines.push(notes.to_string());
+    }
+    lines.join("\n")
+}
+
+pub fn format_outdated(outdated: &[Outdated]) -> String {
+    outdated
+        .iter()
+        .map(|o| {
+            let updates: Vec<String> = [(&o.patch, "patch"), (&o.minor, "minor")]
+                .iter()
+                .filter_map(|(version, kind)| version.as_ref().map(|v| format!("{} ({})", v, kind)))
+                .collect();
+            format!("[cmvm] {} -> {}", o.version, updates.join(", "))
+        })
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+pub fn format_upgrade(upgrade: &Upgrade) -> String {
+    let mut lines = Vec::new();
+    if upgrade.installed {
+        lines.push(format!(
+            "[cmvm] Version {} installed successfully.",
+            upgrade.to
+        ));
+    }
+    if upgrade.current_moved {
+        lines.push(format!("[cmvm] Current version moved to {}.", upgrade.to));
+    }
+    for alias in &upgrade.aliases {
+        lines.push(format!("[cmvm] Alias {} -> {}.", alias, upgrade.to));
+    }
+    if upgrade.removed_old {
+        lines.push(format!("[cmvm] Version {} removed.", upgrade.from));
+    }
+    lines.join("\n")
+}
+
+pub fn format_sync(report: &SyncReport) -> String {
+    let mut lines = Vec::new();
+    for version in &report.already_installed {
+        lines.push(format!("[cmvm] Version {} already installed.", version));
+    }
+    for version in &report.installed {
+        lines.push(format!(
+            "[cmvm] Version {} installed successfully.",
+            version
+        ));
+    }
+    for version in &report.removed {
+        lines.push(format!("[cmvm] Version {} removed.", version));
+    }
+    for (name, version) in &report.aliases {
+        lines.push(format!("[cmvm] Alias {} -> {}.", name, version));
+    }
+    if let Some(version) = &report.default {
+        lines.push(format!("[cmvm] Version {} set as default.", version));
+    }
+    lines.join("\n")
+}
+
+pub fn format_problems(problems: &[Problem]) -> String {
+    problems
+        .iter()
+        .map(|p| format!("[cmvm] Problem: {}\n[cmvm]     Fix: {}", p.message, p.fix))
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use std::collections::BTreeMap;
+
+    fn installed(version: &str, active: bool, aliases: &[&str]) -> InstalledVersion {
+        InstalledVersion {
+            version: version.to_string(),
+            active,
+            path: PathBuf::from("/versions").join(version),
+            aliases: aliases.iter().map(|a| a.to_string()).collect(),
+            store: Store::User,
+        }
+    }
+
+    #[test]
+    fn test_format_installed_versions_marks_active_and_aliases() {
+        let versions = vec![
+            installed("3.20.0", false, &["ci", "legacy"]),
+            installed("3.28.1", true, &[]),
+        ];
+        assert_eq!(
+            format_installed_versions(&versions),
+            "[cmvm]   3.20.0 (ci, legacy)\n[cmvm] * 3.28.1"
+        );
+    }
+
+    #[test]
+    fn test_format_installed_versions_marks_system_store() {
+        let versions = vec![
+            InstalledVersion {
+                store: Store::System,
+                ..installed("3.20.0", false, &["legacy"])
+            },
+            installed("3.28.1", true, &[]),
+        ];
+        assert_eq!(
+            format_installed_versions(&versions),
+            "[cmvm]   3.20.0 (legacy) [system]\n[cmvm] * 3.28.1"
+        );
+    }
+
+    #[test]
+    fn test_installed_version_json_fields() {
+        let json = serde_json::to_value(installed("3.28.1", true, &["ci"])).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "version": "3.28.1",
+                "active": true,
+                "path": "/versions/3.28.1",
+                "aliases": ["ci"],
+                "store": "user"
+            })
+        );
+    }
+
+    #[test]
+    fn test_format_outdated_lists_available_updates() {
+        let outdated = vec![
+            Outdated {
+                version: "3.20.0".to_string(),
+                patch: Some("3.20.6".to_string()),
+                minor: Some("3.28.3".to_string()),
+            },
+            Outdated {
+                version: "3.28.1".to_string(),
+                patch: None,
+                minor: Some("3.29.0".to_string()),
+            },
+        ];
+
+        assert_eq!(
+            format_outdated(&outdated),
+            "[cmvm] 3.20.0 -> 3.20.6 (patch), 3.28.3 (minor)\n[cmvm] 3.28.1 -> 3.29.0 (minor)"
+        );
+    }
+
+    #[test]
+    fn test_format_version_info() {
+        let info = VersionInfo {
+            version: "3.28.1".to_string(),
+            prerelease: false,
+            published_at: Some("2023-12-21T16:28:07Z".to_string()),
+            html_url: None,
+            asset: Some(Asset {
+                name: "cmake-3.28.1-linux-x86_64.tar.gz".to_string(),
+                content_type: "application/gzip".to_string(),
+                browser_download_url: "https://fake-url".to_string(),
+                size: 52428800,
+                updated_at: None,
+            }),
+            installed: false,
+            path: None,
+            aliases: vec![],
+            notes: Some("Bug fixes.\n".to_string()),
+        };
+
+        assert_eq!(
+            format_version_info(&info),
+            [
+                "[cmvm] CMake 3.28.1",
+                "[cmvm] Released: 2023-12-21",
+                "[cmvm] Asset: cmake-3.28.1-linux-x86_64.tar.gz (50.0 MiB)",
+                "[cmvm] Installed: no",
+                "",
+                "Bug fixes.",
+            ]
+            .join("\n")
+        );
+    }
+
+    #[test]
+    fn test_format_version_info_keeps_a_date_that_is_not_rfc_3339() {
+        let info = VersionInfo {
+            version: "3.28.1".to_string(),
+            prerelease: false,
+            published_at: Some("2023-12-2—mirror".to_string()),
+            html_url: None,
+            asset: None,
+            installed: false,
+            path: None,
+            aliases: vec![],
+            notes: None,
+        };
+
+        assert!(format_version_info(&info).contains("[cmvm] Released: 2023-12-2—mirror\n"));
+    }
+
+    fn remote(version: &str, installed: bool) -> RemoteVersion {
+        RemoteVersion {
+            version: version.to_string(),
+            installed,
+            assets: vec![],
+        }
+    }
+
+    #[test]
+    fn test_format_remote_versions_groups_by_minor_series() {
+        let versions = vec![
+            remote("3.9.6", false),
+            remote("3.28.0", false),
+            remote("3.28.1", true),
+            remote("3.28.10", false),
+        ];
+
+        assert_eq!(
+            format_remote_versions(&versions),
+            "[cmvm] 3.9   3.9.6\n[cmvm] 3.28  3.28.0   3.28.1*  3.28.10"
+        );
+    }
+
+    #[test]
+    fn test_format_sync_lists_changes() {
+        let report = SyncReport {
+            installed: vec!["3.28.1".to_string()],
+            already_installed: vec!["3.20.6".to_string()],
+            removed: vec!["3.18.0".to_string()],
+            aliases: BTreeMap::from([("legacy".to_string(), "3.20.6".to_string())]),
+            default: Some("3.28.1".to_string()),
+        };
+
+        assert_eq!(
+            format_sync(&report),
+            [
+                "[cmvm] Version 3.20.6 already installed.",
+                "[cmvm] Version 3.28.1 installed successfully.",
+                "[cmvm] Version 3.18.0 removed.",
+                "[cmvm] Alias legacy -> 3.20.6.",
+                "[cmvm] Version 3.28.1 set as default.",
+            ]
+            .join("\n")
+        );
+        assert_eq!(format_sync(&SyncReport::default()), "");
+    }
+}
@@ -1,31 +1,51 @@
-use anyhow::{bail, Result};
+use anyhow::{anyhow, bail, Result};
 use flate2::read::GzDecoder;
 use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
 use tar::Archive;
 extern crate fs_extra;
+use crate::config::Config;
+use crate::constants::{ARCHIVES_DIR_NAME, FROM_FILE_STAGING_DIR};
+use crate::error::CmvmError;
 use crate::http;
 use crate::storage::Storage;
 use crate::versions::{Asset, Version};
 use crate::{cache, platform};
 use fs_extra::dir;
 
-pub fn get_cmake_release(version: &Version, storage: &impl Storage) -> Result<()> {
+/// Downloads and installs `version`. Network failures surface as
+/// `CmvmError::Network`, anything else as `CmvmError::InstallFailed`.
+pub fn get_cmake_release(version: &Version, config: &Config, storage: &impl Storage) -> Result<()> {
+    let tag_name = version.get_tag_name();
     let assets = filter_platform_assets(version);
 
-    if assets.is_empty() {
-        bail!("[cmvm] No asset found.");
-    }
-
-    let asset = assets.first();
+    let Some(asset) = assets.first() else {
+        bail!(CmvmError::InstallFailed {
+            version: tag_name,
+            reason: "no asset found for this platform.".to_string(),
+        });
+    };
 
-    if let Some(asset) = asset {
-        download(&version.get_tag_name(), asset, storage)?;
-        uncompress(&version.get_tag_name(), asset, storage)?;
-        copy(&version.get_tag_name(), asset, storage)?;
-        clean(&version.get_tag_name(), storage)?;
-    }
+    let install = || -> Result<()> {
+        download(&tag_name, asset, config, storage)?;
+        uncompress(&tag_name, asset, storage)?;
+        let source_dir = storage
+            .get_cache_dir()?
+            .join(&tag_name)
+            .join(asset.name.replace(".tar.gz", ""));
+        copy(&tag_name, &source_dir, storage)?;
+        clean(&tag_name, storage)
+    };
 
-    Ok(())
+    install().map_err(|e| match e.downcast::<CmvmError>() {
+        Ok(e) => e.into(),
+        Err(e) => CmvmError::InstallFailed {
+            version: tag_name.clone(),
+            reason: e.to_string(),
+        }
+        .into(),
+    })
 }
 
 pub fn filter_platform_assets(version: &Version) -> Vec<&Asset> {
@@ -47,9 +67,20 @@ pub fn filter_platform_assets(version: &Version) -> Vec<&Asset> {
         .collect()
 }
 
-fn download(tag_name: &str, asset: &Asset, storage: &impl Storage) -> Result<()> {
+/// Downloads `asset` into `<cache_dir>/<tag_name>`. An archive kept in
+/// `<cache_dir>/archives` by an earlier download is reused instead, and
+/// with `keep_archives` set the new download is kept there. Offline, a kept
+/// archive is the only option.
+pub fn download(
+    tag_name: &str,
+    asset: &Asset,
+    config: &Config,
+    storage: &impl Storage,
+) -> Result<()> {
     let cache_dir = storage.get_cache_dir()?;
     let version_dir_path = cache_dir.join(tag_name);
+    let file_path = version_dir_path.join(&asset.name);
+    let kept_archive = cache_dir.join(ARCHIVES_DIR_NAME).join(&asset.name);
 
     if version_dir_path.exists() {
         cache::delete(&version_dir_path)?;
@@ -57,44 +88,191 @@ fn download(tag_name: &str, asset: &Asset, storage: &impl Storage) -> Result<()>
 
     cache::create_dir(&version_dir_path)?;
 
-    println!("[cmvm] Downloading {}.", asset.browser_download_url);
-    let mut response = http::get(&asset.browser_download_url)?;
-    let file_path = &cache_dir.join(tag_name).join(&asset.name);
-    let mut file = cache::create_file(file_path)?;
+    if kept_archive.is_file() {
+        eprintln!("[cmvm] Using kept archive {}.", kept_archive.display());
+        fs::copy(&kept_archive, &file_path)?;
+        return Ok(());
+    }
+
+    if config.offline() {
+        cache::delete(&version_dir_path)?;
+        bail!(CmvmError::NotInOfflineCache(tag_name.to_string()));
+    }
+
+    let url = config.rewrite_download_url(&asset.browser_download_url);
+    eprintln!("[cmvm] Downloading {}.", url);
+    let mut response = http::download(&url, config.timeout())?;
+    if !response.status().is_success() {
+        bail!(CmvmError::Network(format!(
+            "{} returned {}",
+            url,
+            response.status()
+        )));
+    }
+    let mut file = cache::create_file(&file_path)?;
     response.copy_to(&mut file)?;
 
+    if config.keep_archives() {
+        fs::create_dir_all(kept_archive.parent().unwrap())?;
+        fs::copy(&file_path, &kept_archive)?;
+    }
+
     Ok(())
 }
 
-fn uncompress(tag_name: &str, asset: &Asset, storage: &impl Storage) -> Result<()> {
+pub fn uncompress(tag_name: &str, asset: &Asset, storage: &impl Storage) -> Result<()> {
     let cache_dir = storage.get_cache_dir()?;
     let compressed_file = fs::read(cache_dir.join(tag_name).join(&asset.name))?;
 
     let gz = GzDecoder::new(&*compressed_file);
     let mut archive = Archive::new(gz);
 
-    println!("[cmvm] Uncompressing {}.", asset.name);
+    eprintln!("[cmvm] Uncompressing {}.", asset.name);
     archive.unpack(cache_dir.join(tag_name))?;
 
     Ok(())
 }
 
-fn copy(tag_name: &str, asset: &Asset, storage: &impl Storage) -> Result<()> {
-    let cache_dir = storage.get_cache_dir()?;
-    let versions_dir = storage.get_versions_dir()?;
-    let base_path = &cache_dir
-        .join(tag_name)
-        .join(asset.name.replace(".tar.gz", ""));
+/// Installs an archive from disk, e.g. `cmake-3.28.1-linux-x86_64.tar.gz`,
+/// under `versions/<name>`. Without a name the version is taken from the
+/// archive's top-level directory or, failing that, from `bin/cmake --version`.
+/// Returns the name it was installed under.
+pub fn install_archive(
+    archive: &Path,
+    name: Option<&str>,
+    storage: &impl Storage,
+) -> Result<String> {
+    let asset = local_asset(archive)?;
+    if let Some(name) = name.filter(|n| !is_valid_version_name(n)) {
+        bail!("[cmvm] Invalid version name `{}`.", name);
+    }
+    let staging = FROM_FILE_STAGING_DIR;
+    let staging_dir = storage.get_cache_dir()?.join(staging);
+
+    if staging_dir.exists() {
+        cache::delete(&staging_dir)?;
+    }
+    cache::create_dir(&staging_dir)?;
+    fs::copy(archive, staging_dir.join(&asset.name))?;
+
+    let install = || -> Result<String> {
+        uncompress(staging, &asset, storage)?;
+
+        let Some(source_dir) = cache::ls(&staging_dir)?.into_iter().find(|p| p.is_dir()) else {
+            bail!("archive has no top-level directory.");
+        };
+        let tag_name = match name {
+            Some(name) => name.to_string(),
+            None => version_from_dir_name(&source_dir)
+                .or_else(|| version_from_cmake(&source_dir))
+                .ok_or_else(|| {
+                    anyhow!("cannot tell the cmake version, pass `--as <name>` to name it.")
+                })?,
+        };
+        if storage.get_versions_dir()?.join(&tag_name).exists() {
+            bail!("version {} is already installed.", tag_name);
+        }
 
-    let cmake_cache_dir = match base_path.join("CMake.app/Contents").exists() {
-        true => base_path.join("CMake.app/Contents"),
-        false => base_path.to_path_buf(),
+        copy(&tag_name, &source_dir, storage)?;
+        Ok(tag_name)
     };
 
+    let result = install();
+    clean(staging, storage)?;
+    result.map_err(|e| {
+        CmvmError::InstallFailed {
+            version: name.unwrap_or(&asset.name).to_string(),
+            reason: e.to_string(),
+        }
+        .into()
+    })
+}
+
+/// Describes a `.tar.gz` archive on disk the way `uncompress` expects.
+pub fn local_asset(archive: &Path) -> Result<Asset> {
+    let Some(file_name) = archive
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .filter(|n| n.ends_with(".tar.gz"))
+    else {
+        bail!("[cmvm] {} is not a .tar.gz archive.", archive.display());
+    };
+    if !archive.is_file() {
+        bail!("[cmvm] Archive {} not found.", archive.display());
+    }
+
+    Ok(Asset {
+        name: file_name,
+        content_type: "application/gzip".to_string(),
+        browser_download_url: archive.to_string_lossy().to_string(),
+        size: fs::metadata(archive)?.len(),
+        updated_at: None,
+    })
+}
+
+/// `cmake-3.28.1-linux-x86_64` becomes `3.28.1`, `cmake-3.29.0-rc2-macos-universal`
+/// becomes `3.29.0-rc2`.
+fn version_from_dir_name(source_dir: &Path) -> Option<String> {
+    let dir_name = source_dir.file_name()?.to_string_lossy().to_string();
+    let mut parts = dir_name.strip_prefix("cmake-")?.split('-');
+    let version = parts.next().filter(|v| is_version_number(v))?;
+    match parts.next().filter(|p| p.starts_with("rc")) {
+        Some(rc) => Some(format!("{}-{}", version, rc)),
+        None => Some(version.to_string()),
+    }
+}
+
+/// Reads the version from the first line of `cmake --version`, e.g.
+/// `cmake version 3.28.1`.
+pub fn version_from_cmake(source_dir: &Path) -> Option<String> {
+    let output = Command::new(cmake_root(source_dir).join("bin").join("cmake"))
+        .arg("--version")
+        .output()
+        .ok()?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let version = stdout
+        .lines()
+        .next()?
+        .strip_prefix("cmake version ")?
+        .trim();
+    is_version_number(version.split('-').next()?).then(|| version.to_string())
+}
+
+fn is_version_number(value: &str) -> bool {
+    value.split('.').count() == 3 && value.split('.').all(|p| p.parse::<i32>().is_ok())
+}
+
+fn is_valid_version_name(name: &str) -> bool {
+    !name.is_empty() && !name.starts_with('.') && !name.contains(['/', '\\'])
+}
+
+/// The directory holding `bin/`: the archive root, or `CMake.app/Contents`
+/// in macOS archives.
+fn cmake_root(source_dir: &Path) -> PathBuf {
+    match source_dir.join("CMake.app/Contents").exists() {
+        true => source_dir.join("CMake.app/Contents"),
+        false => source_dir.to_path_buf(),
+    }
+}
+
+/// Installs `source_dir` (the root of an unpacked release or an install
+/// prefix) as `versions/<tag_name>`.
+pub fn copy(tag_name: &str, source_dir: &Path, storage: &impl Storage) -> Result<()> {
+    let versions_dir = storage.get_versions_dir()?;
+    let cmake_cache_dir = cmake_root(source_dir);
+
+    if !cmake_cache_dir.join("bin").is_dir() {
+        bail!("no bin directory found in {}.", source_dir.display());
+    }
+
     let options = dir::CopyOptions::new();
     let mut from_paths: Vec<String> = Vec::new();
 
     for dir in ["bin", "doc", "man", "share"] {
+        // locally built archives may leave out docs or man pages
+        if !cmake_cache_dir.join(dir).exists() {
+            continue;
+        }
         from_paths.push(
             cmake_cache_dir
                 .join(dir)
@@ -112,7 +290,7 @@ fn copy(tag_name: &str, asset: &Asset, storage: &impl Storage) -> Result<()> {
     let destination_dir = versions_dir.join(tag_name);
 
     fs_extra::copy_items(&from_paths, destination_dir, &options)?;
-    println!("[cmvm] Setting up {}.", tag_name);
+    eprintln!("[cmvm] Setting up {}.", tag_name);
 
     Ok(())
 }
@@ -120,20 +298,155 @@ fn copy(tag_name: &str, asset: &Asset, storage: &impl Storage) -> Result<()> {
 fn clean(tag_name: &str, storage: &impl Storage) -> Result<()> {
     let cache_dir = storage.get_cache_dir()?;
     cache::delete(&cache_dir.join(tag_name))?;
-    println!("[cmvm] Cleaning cache.");
+    eprintln!("[cmvm] Cleaning cache.");
     Ok(())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::storage::test_support::storage_in;
     use crate::versions::{Asset, Version};
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+
+    /// Writes `<dir>/<archive_name>` holding `<top_level>/bin/cmake`.
+    fn write_archive(dir: &Path, archive_name: &str, top_level: &str) -> PathBuf {
+        let source = dir.join("source").join(top_level).join("bin");
+        fs::create_dir_all(&source).unwrap();
+        fs::write(source.join("cmake"), "#!/bin/sh\n").unwrap();
+
+        let archive_path = dir.join(archive_name);
+        let gz = GzEncoder::new(
+            fs::File::create(&archive_path).unwrap(),
+            Compression::fast(),
+        );
+        let mut builder = tar::Builder::new(gz);
+        builder
+            .append_dir_all(top_level, dir.join("source").join(top_level))
+            .unwrap();
+        builder.into_inner().unwrap().finish().unwrap();
+        archive_path
+    }
+
+    #[test]
+    fn test_install_archive_infers_version_from_top_level_dir() {
+        let storage = storage_in("cmvm_test_install_archive");
+        let archive = write_archive(
+            &storage.root,
+            "cmake-3.28.1-linux-x86_64.tar.gz",
+            "cmake-3.28.1-linux-x86_64",
+        );
+
+        let tag_name = install_archive(&archive, None, &storage).unwrap();
+        let installed = storage.root.join("versions/3.28.1/bin/cmake").exists();
+        let staging_left = storage.cache_dir.join(FROM_FILE_STAGING_DIR).exists();
+        cache::delete(&storage.root).ok();
+
+        assert_eq!(tag_name, "3.28.1");
+        assert!(installed);
+        assert!(!staging_left);
+    }
+
+    #[test]
+    fn test_install_archive_uses_given_name() {
+        let storage = storage_in("cmvm_test_install_archive_as");
+        let archive = write_archive(&storage.root, "patched.tar.gz", "build");
+
+        let tag_name = install_archive(&archive, Some("3.28.1-patched"), &storage).unwrap();
+        let installed = storage
+            .root
+            .join("versions/3.28.1-patched/bin/cmake")
+            .exists();
+        cache::delete(&storage.root).ok();
+
+        assert_eq!(tag_name, "3.28.1-patched");
+        assert!(installed);
+    }
+
+    fn offline() -> Config {
+        Config {
+            offline: Some(true),
+            ..Config::default()
+        }
+    }
+
+    fn asset(name: &str) -> Asset {
+        Asset {
+            name: name.to_string(),
+            content_type: "application/gzip".to_string(),
+            browser_download_url: "https://unreachable.invalid/cmake.tar.gz".to_string(),
+            size: 0,
+            updated_at: None,
+        }
+    }
+
+    #[test]
+    fn test_fetch_offline_uses_kept_archive() {
+        let storage = storage_in("cmvm_test_fetch_offline_kept");
+        let archives_dir = storage.cache_dir.join(ARCHIVES_DIR_NAME);
+        fs::create_dir_all(&archives_dir).unwrap();
+        let name = "cmake-3.28.1-linux-x86_64.tar.gz";
+        write_archive(&archives_dir, name, "cmake-3.28.1-linux-x86_64");
+
+        let result = download("3.28.1", &asset(name), &offline(), &storage);
+        let fetched = storage.cache_dir.join("3.28.1").join(name).is_file();
+        cache::delete(&storage.root).ok();
+
+        assert!(result.is_ok());
+        assert!(fetched);
+    }
+
+    #[test]
+    fn test_fetch_offline_without_kept_archive_fails() {
+        let storage = storage_in("cmvm_test_fetch_offline_missing");
+
+        let result = download(
+            "3.28.1",
+            &asset("cmake-3.28.1-linux-x86_64.tar.gz"),
+            &offline(),
+            &storage,
+        );
+        let staging_left = storage.cache_dir.join("3.28.1").exists();
+        cache::delete(&storage.root).ok();
+
+        assert_eq!(
+            result.unwrap_err().downcast_ref::<CmvmError>(),
+            Some(&CmvmError::NotInOfflineCache("3.28.1".to_string()))
+        );
+        assert!(!staging_left);
+    }
+
+    #[test]
+    fn test_install_archive_rejects_non_gzip_archives() {
+        let storage = storage_in("cmvm_test_install_archive_zip");
+
+        let result = install_archive(Path::new("cmake-3.28.1-windows-x86_64.zip"), None, &storage);
+        cache::delete(&storage.root).ok();
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_version_from_dir_name() {
+        assert_eq!(
+            version_from_dir_name(Path::new("cmake-3.28.1-linux-x86_64")),
+            Some("3.28.1".to_string())
+        );
+        assert_eq!(
+            version_from_dir_name(Path::new("cmake-3.29.0-rc2-macos-universal")),
+            Some("3.29.0-rc2".to_string())
+        );
+        assert_eq!(version_from_dir_name(Path::new("build")), None);
+    }
 
     fn make_asset(name: &str, content_type: &str) -> Asset {
         Asset {
             name: name.to_string(),
             content_type: content_type.to_string(),
             browser_download_url: "https://fake-url".to_string(),
+            size: 0,
+            updated_at: None,
         }
     }
 
@@ -145,6 +458,9 @@ mod tests {
             prerelease: Some(false),
             tag_name: "v3.22.0".to_string(),
             assets,
+            published_at: None,
+            html_url: None,
+            body: None,
         }
     }
 
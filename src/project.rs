@@ -0,0 +1,162 @@
+use crate::cache;
+use crate::constants::{VERSION_ENV_VAR, VERSION_FILE_NAME};
+use anyhow::{anyhow, Result};
+use std::env;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// Where the requested cmake version came from.
+#[derive(Debug, Clone, PartialEq)]
+pub enum VersionSource {
+    Environment,
+    ProjectFile(PathBuf),
+    Session,
+    Global,
+}
+
+impl fmt::Display for VersionSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VersionSource::Environment => write!(f, "{} environment variable", VERSION_ENV_VAR),
+            VersionSource::ProjectFile(path) => write!(f, "{}", path.display()),
+            VersionSource::Session => write!(f, "shell session"),
+            VersionSource::Global => write!(f, "global default"),
+        }
+    }
+}
+
+/// Walks from `start` up to the filesystem root and returns the first
+/// `.cmake-version` file found.
+pub fn find_version_file(start: &Path) -> Option<PathBuf> {
+    start
+        .ancestors()
+        .map(|dir| dir.join(VERSION_FILE_NAME))
+        .find(|path| path.is_file())
+}
+
+/// Returns the first non-empty, non-comment line of a `.cmake-version` file.
+pub fn read_version_file(path: &Path) -> Result<String> {
+    let contents = cache::open_file(path.to_path_buf())?;
+    contents
+        .lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(String::from)
+        .ok_or_else(|| anyhow!("[cmvm] {} does not contain a version.", path.display()))
+}
+
+pub fn get_project_version(cwd: &Path) -> Result<Option<(String, PathBuf)>> {
+    match find_version_file(cwd) {
+        Some(path) => Ok(Some((read_version_file(&path)?, path))),
+        None => Ok(None),
+    }
+}
+
+/// Returns the version requested for `cwd` by the environment or by a project
+/// file. `None` means the global `current` symlink applies.
+pub fn get_requested_version(cwd: &Path) -> Result<Option<(String, VersionSource)>> {
+    get_requested_version_with(cwd, |name| env::var(name).ok())
+}
+
+/// `get_requested_version` reading environment variables through `env`.
+pub fn get_requested_version_with(
+    cwd: &Path,
+    env: impl Fn(&str) -> Option<String>,
+) -> Result<Option<(String, VersionSource)>> {
+    if let Some(version) = env(VERSION_ENV_VAR) {
+        if !version.trim().is_empty() {
+            return Ok(Some((
+                version.trim().to_string(),
+                VersionSource::Environment,
+            )));
+        }
+    }
+
+    Ok(
+        get_project_version(cwd)?
+            .map(|(version, path)| (version, VersionSource::ProjectFile(path))),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use std::fs;
+
+    #[test]
+    fn test_find_version_file_walks_up() {
+        let base = env::temp_dir().join("cmvm_test_project_walk_up");
+        let _ = fs::remove_dir_all(&base);
+        let nested = base.join("src").join("lib");
+        fs::create_dir_all(&nested).unwrap();
+        fs::write(base.join(VERSION_FILE_NAME), "3.28\n").unwrap();
+
+        let found = find_version_file(&nested);
+        let _ = fs::remove_dir_all(&base);
+
+        assert_eq!(found, Some(base.join(VERSION_FILE_NAME)));
+    }
+
+    #[test]
+    fn test_find_version_file_prefers_closest() {
+        let base = env::temp_dir().join("cmvm_test_project_closest");
+        let _ = fs::remove_dir_all(&base);
+        let nested = base.join("sub");
+        fs::create_dir_all(&nested).unwrap();
+        fs::write(base.join(VERSION_FILE_NAME), "3.20\n").unwrap();
+        fs::write(nested.join(VERSION_FILE_NAME), "3.28\n").unwrap();
+
+        let found = get_project_version(&nested).unwrap();
+        let _ = fs::remove_dir_all(&base);
+
+        assert_eq!(
+            found,
+            Some(("3.28".to_string(), nested.join(VERSION_FILE_NAME)))
+        );
+    }
+
+    #[test]
+    fn test_read_version_file_skips_comments_and_blank_lines() {
+        let base = env::temp_dir().join("cmvm_test_project_read");
+        let _ = fs::remove_dir_all(&base);
+        fs::create_dir_all(&base).unwrap();
+        let path = base.join(VERSION_FILE_NAME);
+        fs::write(&path, "# pinned for CI\n\n  3.27.9  \n").unwrap();
+
+        let version = read_version_file(&path).unwrap();
+        fs::write(&path, "\n").unwrap();
+        let empty = read_version_file(&path);
+        let _ = fs::remove_dir_all(&base);
+
+        assert_eq!(version, "3.27.9");
+        assert!(empty.is_err());
+    }
+
+    #[test]
+    fn test_requested_version_prefers_environment_over_file() {
+        let base = env::temp_dir().join("cmvm_test_project_requested");
+        let _ = fs::remove_dir_all(&base);
+        fs::create_dir_all(&base).unwrap();
+        fs::write(base.join(VERSION_FILE_NAME), "3.20\n").unwrap();
+
+        let from_env = get_requested_version_with(&base, |name| {
+            (name == VERSION_ENV_VAR).then(|| " 3.28 ".to_string())
+        })
+        .unwrap();
+        let from_file = get_requested_version_with(&base, |_| None).unwrap();
+        let _ = fs::remove_dir_all(&base);
+
+        assert_eq!(
+            from_env,
+            Some(("3.28".to_string(), VersionSource::Environment))
+        );
+        assert_eq!(
+            from_file,
+            Some((
+                "3.20".to_string(),
+                VersionSource::ProjectFile(base.join(VERSION_FILE_NAME))
+            ))
+        );
+    }
+}
@@ -0,0 +1,259 @@
+use crate::aliases;
+use crate::multishell;
+use crate::storage::{Storage, Store};
+use crate::versions::Version;
+use anyhow::{bail, Result};
+use std::collections::{BTreeMap, HashSet};
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+
+/// Which installed versions to keep. A version is removed when none of the
+/// given policies keeps it. Active and aliased versions are always kept.
+#[derive(Debug, Clone, Default)]
+pub struct PrunePolicy {
+    pub keep_newest: Option<usize>,
+    pub keep_latest_patch: bool,
+    pub unused_days: Option<u64>,
+}
+
+#[derive(Debug, Clone)]
+pub struct PruneCandidate {
+    pub version: Version,
+    pub size: u64,
+}
+
+pub fn plan(policy: &PrunePolicy, storage: &impl Storage) -> Result<Vec<PruneCandidate>> {
+    if policy.keep_newest.is_none() && !policy.keep_latest_patch && policy.unused_days.is_none() {
+        bail!(
+            "[cmvm] No prune policy given. Use `--keep`, `--keep-latest-patch` or `--unused-days`."
+        );
+    }
+
+    let versions_dir = storage.get_versions_dir()?;
+    // the system store is left to whoever administers it
+    let mut installed = Version::all_installed_in(Store::User, storage)?;
+    installed.sort_by_key(|v| (v.major, v.minor, v.patch));
+
+    let mut kept: HashSet<String> = aliases::all(storage)?.into_values().collect();
+    // in a `--multishell` shell `current` is the session link, so the global
+    // link and those of the other running shells count as active too
+    let mut active_links = vec![
+        storage.get_current_version_dir()?,
+        storage.get_data_dir()?.join("current"),
+    ];
+    active_links.extend(multishell::live_links(storage)?);
+    kept.extend(active_links.iter().filter_map(|link| {
+        link.read_link()
+            .ok()
+            .and_then(|target| target.file_name().map(|n| n.to_string_lossy().to_string()))
+    }));
+
+    if let Some(keep_newest) = policy.keep_newest {
+        kept.extend(
+            installed
+                .iter()
+                .rev()
+                .take(keep_newest)
+                .map(|v| v.get_tag_name()),
+        );
+    }
+
+    if policy.keep_latest_patch {
+        let mut latest_per_minor: BTreeMap<(Option<i32>, Option<i32>), String> = BTreeMap::new();
+        for version in installed.iter().filter(|v| !v.prerelease.unwrap_or(false)) {
+            latest_per_minor.insert((version.major, version.minor), version.get_tag_name());
+        }
+        kept.extend(latest_per_minor.into_values());
+    }
+
+    if let Some(unused_days) = policy.unused_days {
+        let threshold = SystemTime::now() - Duration::from_secs(unused_days * 24 * 60 * 60);
+        kept.extend(
+            installed
+                .iter()
+                .filter(|v| last_used(&versions_dir.join(v.get_tag_name())) > threshold)
+                .map(|v| v.get_tag_name()),
+        );
+    }
+
+    let mut candidates = Vec::new();
+    for version in installed {
+        if kept.contains(&version.get_tag_name()) {
+            continue;
+        }
+        let size = fs_extra::dir::get_size(versions_dir.join(version.get_tag_name()))?;
+        candidates.push(PruneCandidate { version, size });
+    }
+    Ok(candidates)
+}
+
+/// Best guess at when a version was last run: the access time of its
+/// `bin/cmake`, or the modification time of its directory when that is newer.
+fn last_used(version_path: &Path) -> SystemTime {
+    let accessed = version_path
+        .join("bin")
+        .join("cmake")
+        .metadata()
+        .and_then(|m| m.accessed())
+        .unwrap_or(SystemTime::UNIX_EPOCH);
+    let modified = version_path
+        .metadata()
+        .and_then(|m| m.modified())
+        .unwrap_or(SystemTime::UNIX_EPOCH);
+    accessed.max(modified)
+}
+
+pub fn format_size(bytes: u64) -> String {
+    let units = ["B", "KiB", "MiB", "GiB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < units.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    match unit {
+        0 => format!("{} {}", bytes, units[0]),
+        _ => format!("{:.1} {}", size, units[unit]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::test_support::MockStorage;
+    use pretty_assertions::assert_eq;
+    use std::env;
+    use std::fs;
+
+    fn setup(name: &str, tags: &[&str]) -> MockStorage {
+        let cache_dir = env::temp_dir().join(name);
+        let _ = fs::remove_dir_all(&cache_dir);
+        for tag in tags {
+            let bin_dir = cache_dir.join("versions").join(tag).join("bin");
+            fs::create_dir_all(&bin_dir).unwrap();
+            fs::write(bin_dir.join("cmake"), "cmake").unwrap();
+        }
+        MockStorage::new(&cache_dir)
+    }
+
+    fn tags(candidates: &[PruneCandidate]) -> Vec<String> {
+        candidates
+            .iter()
+            .map(|c| c.version.get_tag_name())
+            .collect()
+    }
+
+    #[test]
+    fn test_plan_requires_a_policy() {
+        let storage = setup("cmvm_test_prune_no_policy", &["3.20.0"]);
+        let result = plan(&PrunePolicy::default(), &storage);
+        let _ = fs::remove_dir_all(&storage.cache_dir);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_plan_keeps_newest_active_and_aliased() {
+        let storage = setup(
+            "cmvm_test_prune_keep_newest",
+            &["3.18.0", "3.20.0", "3.22.0", "3.28.1", "3.29.0"],
+        );
+        let versions_dir = storage.cache_dir.join("versions");
+        std::os::unix::fs::symlink(
+            versions_dir.join("3.18.0"),
+            storage.cache_dir.join("current"),
+        )
+        .unwrap();
+        aliases::set("legacy", "3.20.0", &storage).unwrap();
+        let policy = PrunePolicy {
+            keep_newest: Some(2),
+            ..Default::default()
+        };
+
+        let candidates = plan(&policy, &storage).unwrap();
+        let _ = fs::remove_dir_all(&storage.cache_dir);
+
+        assert_eq!(tags(&candidates), vec!["3.22.0"]);
+        assert_eq!(candidates[0].size, 5);
+    }
+
+    #[test]
+    fn test_plan_keeps_versions_active_in_any_shell() {
+        let root = env::temp_dir().join("cmvm_test_prune_multishell");
+        let _ = fs::remove_dir_all(&root);
+        for tag in ["3.18.0", "3.20.0", "3.22.0", "3.24.0", "3.28.1"] {
+            fs::create_dir_all(root.join("versions").join(tag)).unwrap();
+        }
+        let mut storage = MockStorage::new(&root);
+        let versions_dir = root.join("versions");
+        let multishells_dir = storage.get_multishells_dir().unwrap();
+        fs::create_dir_all(&multishells_dir).unwrap();
+        let pid = std::process::id();
+        let links = [
+            (root.join("current"), "3.18.0"),
+            (multishells_dir.join(format!("{}_1", pid)), "3.20.0"),
+            (multishells_dir.join(format!("{}_2", pid)), "3.22.0"),
+            (multishells_dir.join(format!("{}_3", i32::MAX)), "3.24.0"),
+        ];
+        for (link, tag) in &links {
+            std::os::unix::fs::symlink(versions_dir.join(tag), link).unwrap();
+        }
+        let policy = PrunePolicy {
+            keep_newest: Some(1),
+            ..Default::default()
+        };
+
+        storage.session_link = Some(links[1].0.clone());
+        let candidates = plan(&policy, &storage);
+        let _ = fs::remove_dir_all(&root);
+
+        assert_eq!(tags(&candidates.unwrap()), vec!["3.24.0"]);
+    }
+
+    #[test]
+    fn test_plan_keeps_latest_patch_per_minor() {
+        let storage = setup(
+            "cmvm_test_prune_latest_patch",
+            &["3.20.0", "3.20.6", "3.28.0", "3.28.1", "3.28.3"],
+        );
+        let policy = PrunePolicy {
+            keep_latest_patch: true,
+            ..Default::default()
+        };
+
+        let candidates = plan(&policy, &storage).unwrap();
+        let _ = fs::remove_dir_all(&storage.cache_dir);
+
+        assert_eq!(tags(&candidates), vec!["3.20.0", "3.28.0", "3.28.1"]);
+    }
+
+    #[test]
+    fn test_plan_removes_versions_unused_for_days() {
+        let storage = setup("cmvm_test_prune_unused", &["3.20.0", "3.28.1"]);
+        let old = SystemTime::now() - Duration::from_secs(90 * 24 * 60 * 60);
+        for path in [
+            storage.cache_dir.join("versions/3.20.0/bin/cmake"),
+            storage.cache_dir.join("versions/3.20.0"),
+        ] {
+            fs::File::open(&path)
+                .unwrap()
+                .set_times(fs::FileTimes::new().set_accessed(old).set_modified(old))
+                .unwrap();
+        }
+        let policy = PrunePolicy {
+            unused_days: Some(30),
+            ..Default::default()
+        };
+
+        let candidates = plan(&policy, &storage).unwrap();
+        let _ = fs::remove_dir_all(&storage.cache_dir);
+
+        assert_eq!(tags(&candidates), vec!["3.20.0"]);
+    }
+
+    #[test]
+    fn test_format_size() {
+        assert_eq!(format_size(512), "512 B");
+        assert_eq!(format_size(1536), "1.5 KiB");
+        assert_eq!(format_size(48 * 1024 * 1024), "48.0 MiB");
+    }
+}
@@ -1,7 +1,9 @@
 use crate::cache;
-use crate::constants::{BASE_URL, RELEASES_FILE_NAME};
+use crate::config::Config;
+use crate::constants::RELEASES_FILE_NAME;
+use crate::error::CmvmError;
 use crate::http;
-use crate::storage::Storage;
+use crate::storage::{Storage, Store};
 use crate::versions::Version;
 use anyhow::{bail, Result};
 use serde_json::Value;
@@ -9,59 +11,94 @@ use std::path::PathBuf;
 use std::thread::spawn;
 use std::{fs, io::Write};
 
-pub fn build_cache(storage: &impl Storage) -> Result<()> {
+pub fn build_cache(config: &Config, storage: &impl Storage) -> Result<()> {
     let cache_dir = storage.get_cache_dir()?;
 
+    if config.offline() {
+        if !cache_dir.join(RELEASES_FILE_NAME).exists() {
+            bail!(
+                "[cmvm] No cached release index in {}. Run `cmvm list-remote` once without --offline.",
+                cache_dir.display()
+            );
+        }
+        return Ok(());
+    }
+
     match cache_dir.join(RELEASES_FILE_NAME).exists() {
         true => {
-            spawn(|| {
-                if cache_releases(cache_dir, None).is_err() {
-                    println!("[cmvm] Failed to fetch remote versions");
+            let config = config.clone();
+            spawn(move || {
+                if cache_releases(&config, cache_dir, None).is_err() {
+                    eprintln!("[cmvm] Failed to fetch remote versions");
                 }
             });
         }
         false => {
-            println!("[cmvm] Fetching versions for the first time...");
-            if cache_releases(cache_dir, None).is_err() {
-                println!("[cmvm] Failed to fetch remote versions");
-            }
+            eprintln!("[cmvm] Fetching versions for the first time...");
+            cache_releases(config, cache_dir, None)?;
         }
     }
     Ok(())
 }
 
+/// The error for `version` missing from the release index. Offline, the
+/// cached index may simply predate it.
+pub fn version_not_found(version: &str, config: &Config) -> CmvmError {
+    match config.offline() {
+        true => CmvmError::NotInOfflineCache(version.to_string()),
+        false => CmvmError::VersionNotFound(version.to_string()),
+    }
+}
+
 pub fn get_release(version: &str, storage: &impl Storage) -> Result<Option<Version>> {
     let releases = Version::all_from_cache(storage)?;
     let release = releases.iter().find(|v| v.get_tag_name() == version);
     Ok(release.cloned())
 }
 
+/// Removes `version` from the user's `versions/`. Versions in the system
+/// store are only removed by `cmvm uninstall --system`, which points
+/// `versions/` at the system store.
 pub fn delete_cache_release(version: &str, storage: &impl Storage) -> Result<()> {
-    let versions_dir = storage.get_versions_dir()?;
     let current_version_dir = storage.get_current_version_dir()?;
-    if let Some(release) = get_release(version, storage)? {
-        let version_path = versions_dir.join(release.get_tag_name());
-        if !version_path.exists() {
-            bail!("[cmvm] Version {} is not installed.", version);
-        }
-        if current_version_dir.read_link().ok().as_ref() == Some(&version_path) {
-            cache::delete(&current_version_dir)?;
-        }
-        cache::delete(version_path.as_path())?;
-    } else {
-        bail!("[cmvm] Version {} not found.", version);
+    let tag_name = version.strip_prefix('v').unwrap_or(version);
+
+    let version_path = match storage.find_version_dir(tag_name)? {
+        Some((path, Store::User)) => path,
+        Some((path, Store::System)) => bail!(
+            "[cmvm] Version {} is installed in the system store at {}. Use `cmvm uninstall --system {}` to remove it.",
+            tag_name,
+            path.display(),
+            tag_name
+        ),
+        None => match get_release(version, storage).ok().flatten() {
+            Some(_) => bail!(CmvmError::NotInstalled(version.to_string())),
+            None => bail!(CmvmError::VersionNotFound(version.to_string())),
+        },
+    };
+    if current_version_dir.read_link().ok().as_ref() == Some(&version_path) {
+        cache::delete(&current_version_dir)?;
     }
+    cache::delete(version_path.as_path())?;
 
     Ok(())
 }
 
-fn cache_releases(cache_dir: PathBuf, page: Option<i32>) -> Result<()> {
+fn cache_releases(config: &Config, cache_dir: PathBuf, page: Option<i32>) -> Result<()> {
+    let api_url = config.api_url();
     let current_page = page.unwrap_or(1);
     let first_page = current_page == 1;
-    let mut response = http::get(format!("{}?page={}", BASE_URL, current_page).as_str())?;
+    let mut response = http::get(
+        format!("{}?page={}", api_url, current_page).as_str(),
+        config.timeout(),
+    )?;
 
     if !response.status().is_success() {
-        bail!("[cmvm] Something went wrong");
+        bail!(CmvmError::Network(format!(
+            "{} returned {}",
+            api_url,
+            response.status()
+        )));
     }
 
     let current_page_file = cache_dir.join(format!("{}.json", current_page));
@@ -77,7 +114,7 @@ fn cache_releases(cache_dir: PathBuf, page: Option<i32>) -> Result<()> {
         if let Some(link_header) = response.headers().get("link") {
             let pages = get_number_of_pages(link_header.to_str()?)?;
             for page in 2..=pages {
-                cache_releases(cache_dir.clone(), Some(page))?;
+                cache_releases(config, cache_dir.clone(), Some(page))?;
             }
             merge(cache_dir, pages)?;
         } else {
@@ -131,29 +168,9 @@ fn get_number_of_pages(link_header: &str) -> Result<i32> {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use anyhow::Result;
+    use crate::storage::test_support::MockStorage;
     use serde_json::json;
     use std::env;
-    use std::path::PathBuf;
-
-    struct MockStorage {
-        cache_dir: PathBuf,
-    }
-
-    impl Storage for MockStorage {
-        fn get_cache_dir(&self) -> Result<PathBuf> {
-            Ok(self.cache_dir.clone())
-        }
-        fn get_data_dir(&self) -> Result<PathBuf> {
-            Ok(self.cache_dir.clone())
-        }
-        fn get_current_version_dir(&self) -> Result<PathBuf> {
-            Ok(self.cache_dir.join("current"))
-        }
-        fn get_versions_dir(&self) -> Result<PathBuf> {
-            Ok(self.cache_dir.join("versions"))
-        }
-    }
 
     #[test]
     fn test_releases() {
@@ -182,9 +199,7 @@ mod tests {
             .write_all(raw_release.to_string().as_bytes())
             .ok();
 
-        let storage = MockStorage {
-            cache_dir: cache_dir.clone(),
-        };
+        let storage = MockStorage::new(&cache_dir);
         let releases = Version::all_from_cache(&storage).unwrap();
         let release = &releases[0];
 
@@ -222,9 +237,7 @@ mod tests {
             .write_all(raw_release.to_string().as_bytes())
             .ok();
 
-        let storage = MockStorage {
-            cache_dir: cache_dir.clone(),
-        };
+        let storage = MockStorage::new(&cache_dir);
         let releases = Version::all_from_cache(&storage).unwrap();
         let release = &releases[0];
 
@@ -276,9 +289,7 @@ mod tests {
         ]);
         write_releases_cache(&cache_dir, &raw_releases);
 
-        let storage = MockStorage {
-            cache_dir: cache_dir.clone(),
-        };
+        let storage = MockStorage::new(&cache_dir);
         let release = get_release("3.25.0", &storage).unwrap();
 
         cache::delete(&cache_dir).ok();
@@ -299,9 +310,7 @@ mod tests {
         ]);
         write_releases_cache(&cache_dir, &raw_releases);
 
-        let storage = MockStorage {
-            cache_dir: cache_dir.clone(),
-        };
+        let storage = MockStorage::new(&cache_dir);
         let release = get_release("3.99.0", &storage).unwrap();
 
         cache::delete(&cache_dir).ok();
@@ -328,13 +337,14 @@ mod tests {
             .write_all(raw_releases.to_string().as_bytes())
             .unwrap();
 
-        let storage = MockStorage {
-            cache_dir: cache_dir.clone(),
-        };
+        let storage = MockStorage::new(&cache_dir);
         // Version is in cache but directory does not exist → should error
         let result = delete_cache_release("3.25.0", &storage);
         cache::delete(&cache_dir).ok();
-        assert!(result.is_err());
+        assert_eq!(
+            result.unwrap_err().downcast_ref::<CmvmError>(),
+            Some(&CmvmError::NotInstalled("3.25.0".to_string()))
+        );
     }
 
     #[test]
@@ -350,12 +360,32 @@ mod tests {
         ]);
         write_releases_cache(&cache_dir, &raw_releases);
 
-        let storage = MockStorage {
-            cache_dir: cache_dir.clone(),
-        };
+        let storage = MockStorage::new(&cache_dir);
         let result = delete_cache_release("3.99.0", &storage);
         cache::delete(&cache_dir).ok();
-        assert!(result.is_err());
+        assert_eq!(
+            result.unwrap_err().downcast_ref::<CmvmError>(),
+            Some(&CmvmError::VersionNotFound("3.99.0".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_delete_cache_release_removes_version_missing_from_cache() {
+        let cache_dir = env::temp_dir().join("cmvm_test_delete_uncached");
+        let _ = std::fs::remove_dir_all(&cache_dir);
+        let version_path = cache_dir.join("versions").join("3.20.0");
+        std::fs::create_dir_all(&version_path).unwrap();
+        std::os::unix::fs::symlink(&version_path, cache_dir.join("current")).unwrap();
+
+        let storage = MockStorage::new(&cache_dir);
+        let result = delete_cache_release("3.20.0", &storage);
+        let version_exists = version_path.exists();
+        let current_exists = cache_dir.join("current").symlink_metadata().is_ok();
+        cache::delete(&cache_dir).ok();
+
+        assert!(result.is_ok());
+        assert!(!version_exists);
+        assert!(!current_exists);
     }
 
     #[test]
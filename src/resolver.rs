@@ -0,0 +1,352 @@
+use crate::storage::Storage;
+use crate::versions::Version;
+use crate::{aliases, package};
+use anyhow::{bail, Result};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Op {
+    Eq,
+    Gt,
+    Ge,
+    Lt,
+    Le,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Comparator {
+    op: Op,
+    version: (i32, i32, i32),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum VersionRequest {
+    Latest,
+    Exact(String),
+    Partial(Vec<i32>),
+    Range(Vec<Comparator>),
+}
+
+impl VersionRequest {
+    pub fn parse(request: &str) -> Result<VersionRequest> {
+        let request = request.trim();
+        let request = request.strip_prefix('v').unwrap_or(request);
+
+        if request.is_empty() {
+            bail!("[cmvm] Empty version request.");
+        }
+
+        if request == "latest" {
+            return Ok(VersionRequest::Latest);
+        }
+
+        if request.contains(['>', '<', '=', '~', '^', ',']) {
+            let mut comparators = Vec::new();
+            for part in request.split(',') {
+                comparators.extend(parse_comparator(part.trim())?);
+            }
+            return Ok(VersionRequest::Range(comparators));
+        }
+
+        match parse_numbers(request) {
+            Some(parts) if parts.len() < 3 => Ok(VersionRequest::Partial(parts)),
+            _ => Ok(VersionRequest::Exact(request.to_string())),
+        }
+    }
+
+    pub fn matches(&self, version: &Version) -> bool {
+        let triple = get_triple(version);
+        match self {
+            VersionRequest::Latest => true,
+            VersionRequest::Exact(tag_name) => &version.get_tag_name() == tag_name,
+            VersionRequest::Partial(parts) => parts
+                .iter()
+                .zip([triple.0, triple.1, triple.2])
+                .all(|(expected, actual)| *expected == actual),
+            VersionRequest::Range(comparators) => comparators.iter().all(|c| match c.op {
+                Op::Eq => triple == c.version,
+                Op::Gt => triple > c.version,
+                Op::Ge => triple >= c.version,
+                Op::Lt => triple < c.version,
+                Op::Le => triple <= c.version,
+            }),
+        }
+    }
+
+    fn is_exact(&self) -> bool {
+        matches!(self, VersionRequest::Exact(_))
+    }
+
+    /// Returns the newest version matching the request. Prereleases are only
+    /// considered when they are asked for by their exact tag name.
+    pub fn best_match(&self, versions: Vec<Version>) -> Option<Version> {
+        self.best_match_with(versions, false)
+    }
+
+    /// Like `best_match`, but with `prereleases` any matching prerelease is
+    /// a candidate too, so `latest` may resolve to a release candidate.
+    pub fn best_match_with(&self, versions: Vec<Version>, prereleases: bool) -> Option<Version> {
+        versions
+            .into_iter()
+            .filter(|v| prereleases || self.is_exact() || !v.is_rc())
+            .filter(|v| self.matches(v))
+            .max()
+    }
+}
+
+/// Resolves a version request against the releases cached in `releases.json`.
+pub fn resolve_remote(request: &str, storage: &impl Storage) -> Result<Option<Version>> {
+    resolve_remote_with(request, false, storage)
+}
+
+/// Resolves a version request against `releases.json`, considering
+/// prereleases as well when `prereleases` is set.
+pub fn resolve_remote_with(
+    request: &str,
+    prereleases: bool,
+    storage: &impl Storage,
+) -> Result<Option<Version>> {
+    let request = VersionRequest::parse(request)?;
+    let versions: Vec<Version> = Version::all_from_cache(storage)?
+        .into_iter()
+        .filter(|v| request.is_exact() || !package::filter_platform_assets(v).is_empty())
+        .collect();
+    Ok(request.best_match_with(versions, prereleases))
+}
+
+/// Resolves a version request or an alias name against the versions
+/// installed in `versions/`.
+pub fn resolve_installed(request: &str, storage: &impl Storage) -> Result<Option<Version>> {
+    if let Some(tag_name) = aliases::get(request.trim(), storage)? {
+        return Ok(Version::all_installed(storage)?
+            .into_iter()
+            .find(|v| v.get_tag_name() == tag_name));
+    }
+
+    let request = VersionRequest::parse(request)?;
+    Ok(request.best_match(Version::all_installed(storage)?))
+}
+
+pub fn get_triple(version: &Version) -> (i32, i32, i32) {
+    (
+        version.major.unwrap_or(0),
+        version.minor.unwrap_or(0),
+        version.patch.unwrap_or(0),
+    )
+}
+
+fn parse_numbers(value: &str) -> Option<Vec<i32>> {
+    let parts: Vec<&str> = value.split('.').collect();
+    if parts.len() > 3 {
+        return None;
+    }
+    parts.iter().map(|p| p.parse::<i32>().ok()).collect()
+}
+
+/// Returns the smallest version that is greater than every version starting
+/// with `parts`, e.g. `3.22` becomes `3.23.0` and `3` becomes `4.0.0`.
+fn next_after(parts: &[i32]) -> (i32, i32, i32) {
+    match parts {
+        [major] => (major + 1, 0, 0),
+        [major, minor] => (*major, minor + 1, 0),
+        [major, minor, patch] => (*major, *minor, patch + 1),
+        _ => (0, 0, 0),
+    }
+}
+
+fn to_triple(parts: &[i32]) -> (i32, i32, i32) {
+    (
+        parts.first().copied().unwrap_or(0),
+        parts.get(1).copied().unwrap_or(0),
+        parts.get(2).copied().unwrap_or(0),
+    )
+}
+
+fn parse_comparator(value: &str) -> Result<Vec<Comparator>> {
+    let (op, rest) = ["~", "^", ">=", "<=", ">", "<", "="]
+        .iter()
+        .find_map(|op| value.strip_prefix(op).map(|rest| (*op, rest.trim())))
+        .unwrap_or(("=", value));
+    let rest = rest.strip_prefix('v').unwrap_or(rest);
+
+    let Some(parts) = parse_numbers(rest).filter(|p| !p.is_empty()) else {
+        bail!("[cmvm] Invalid version constraint `{}`.", value);
+    };
+
+    let lower = Comparator {
+        op: Op::Ge,
+        version: to_triple(&parts),
+    };
+
+    let comparators = match op {
+        "~" => {
+            let upper = match parts.as_slice() {
+                [major] => next_after(&[*major]),
+                [major, minor, ..] => next_after(&[*major, *minor]),
+                _ => unreachable!(),
+            };
+            vec![
+                lower,
+                Comparator {
+                    op: Op::Lt,
+                    version: upper,
+                },
+            ]
+        }
+        "^" => vec![
+            lower,
+            Comparator {
+                op: Op::Lt,
+                version: next_after(&parts[..1]),
+            },
+        ],
+        "=" if parts.len() < 3 => vec![
+            lower,
+            Comparator {
+                op: Op::Lt,
+                version: next_after(&parts),
+            },
+        ],
+        "<=" if parts.len() < 3 => vec![Comparator {
+            op: Op::Lt,
+            version: next_after(&parts),
+        }],
+        ">" if parts.len() < 3 => vec![Comparator {
+            op: Op::Ge,
+            version: next_after(&parts),
+        }],
+        _ => vec![Comparator {
+            op: match op {
+                ">=" => Op::Ge,
+                "<=" => Op::Le,
+                ">" => Op::Gt,
+                "<" => Op::Lt,
+                _ => Op::Eq,
+            },
+            version: to_triple(&parts),
+        }],
+    };
+
+    Ok(comparators)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cache;
+    use crate::storage::test_support::MockStorage;
+    use pretty_assertions::assert_eq;
+    use std::env;
+
+    fn versions(tags: &[&str]) -> Vec<Version> {
+        tags.iter().map(|t| Version::from_tag_name(t)).collect()
+    }
+
+    fn best(request: &str, tags: &[&str]) -> Option<String> {
+        VersionRequest::parse(request)
+            .unwrap()
+            .best_match(versions(tags))
+            .map(|v| v.get_tag_name())
+    }
+
+    #[test]
+    fn test_parse_partial_and_exact() {
+        assert_eq!(
+            VersionRequest::parse("3.28").unwrap(),
+            VersionRequest::Partial(vec![3, 28])
+        );
+        assert_eq!(
+            VersionRequest::parse("v3.28.1").unwrap(),
+            VersionRequest::Exact("3.28.1".to_string())
+        );
+        assert_eq!(
+            VersionRequest::parse("latest").unwrap(),
+            VersionRequest::Latest
+        );
+    }
+
+    #[test]
+    fn test_parse_rejects_invalid_constraint() {
+        assert!(VersionRequest::parse(">=abc").is_err());
+        assert!(VersionRequest::parse("").is_err());
+    }
+
+    #[test]
+    fn test_partial_picks_newest_patch() {
+        let tags = ["3.27.9", "3.28.0", "3.28.3", "3.29.0"];
+        assert_eq!(best("3.28", &tags), Some("3.28.3".to_string()));
+        assert_eq!(best("3", &tags), Some("3.29.0".to_string()));
+        assert_eq!(best("4", &tags), None);
+    }
+
+    #[test]
+    fn test_latest_skips_prereleases() {
+        let tags = ["3.28.3", "3.29.0-rc2"];
+        assert_eq!(best("latest", &tags), Some("3.28.3".to_string()));
+        assert_eq!(best("3.29.0-rc2", &tags), Some("3.29.0-rc2".to_string()));
+    }
+
+    #[test]
+    fn test_pre_allows_release_candidates() {
+        let tags = ["3.28.3", "3.29.0-rc1", "3.29.0-rc2"];
+        let latest = |prereleases| {
+            VersionRequest::Latest
+                .best_match_with(versions(&tags), prereleases)
+                .map(|v| v.get_tag_name())
+        };
+
+        assert_eq!(latest(false), Some("3.28.3".to_string()));
+        assert_eq!(latest(true), Some("3.29.0-rc2".to_string()));
+    }
+
+    #[test]
+    fn test_final_release_beats_its_release_candidates() {
+        let tags = ["3.29.0-rc2", "3.29.0", "3.29.0-rc1"];
+        let best = VersionRequest::Latest.best_match_with(versions(&tags), true);
+
+        assert_eq!(best.unwrap().get_tag_name(), "3.29.0");
+    }
+
+    #[test]
+    fn test_range_constraints() {
+        let tags = ["3.19.8", "3.20.0", "3.22.1", "3.24.4", "3.25.0", "4.0.1"];
+        assert_eq!(best(">=3.20,<3.25", &tags), Some("3.24.4".to_string()));
+        assert_eq!(best("~3.22", &tags), Some("3.22.1".to_string()));
+        assert_eq!(best("^3", &tags), Some("3.25.0".to_string()));
+        assert_eq!(best("<=3.24", &tags), Some("3.24.4".to_string()));
+        assert_eq!(best(">3.25", &tags), Some("4.0.1".to_string()));
+        assert_eq!(best("<3.20", &tags), Some("3.19.8".to_string()));
+    }
+
+    #[test]
+    fn test_resolve_installed_uses_versions_dir() {
+        let cache_dir = env::temp_dir().join("cmvm_test_resolve_installed");
+        let _ = std::fs::remove_dir_all(&cache_dir);
+        let versions_dir = cache_dir.join("versions");
+        for tag in ["3.20.0", "3.28.1", "3.28.4"] {
+            std::fs::create_dir_all(versions_dir.join(tag)).unwrap();
+        }
+        let storage = MockStorage::new(&cache_dir);
+
+        let resolved = resolve_installed("3.28", &storage).unwrap();
+        cache::delete(&cache_dir).ok();
+
+        assert_eq!(resolved.unwrap().get_tag_name(), "3.28.4");
+    }
+
+    #[test]
+    fn test_resolve_installed_follows_aliases() {
+        let cache_dir = env::temp_dir().join("cmvm_test_resolve_alias");
+        let _ = std::fs::remove_dir_all(&cache_dir);
+        let versions_dir = cache_dir.join("versions");
+        for tag in ["3.20.0", "3.28.4"] {
+            std::fs::create_dir_all(versions_dir.join(tag)).unwrap();
+        }
+        let storage = MockStorage::new(&cache_dir);
+        aliases::set("legacy", "3.20.0", &storage).unwrap();
+
+        let resolved = resolve_installed("legacy", &storage).unwrap();
+        cache::delete(&cache_dir).ok();
+
+        assert_eq!(resolved.unwrap().get_tag_name(), "3.20.0");
+    }
+}
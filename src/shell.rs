@@ -0,0 +1,178 @@
+use crate::constants::MULTISHELL_ENV_VAR;
+use crate::project;
+use crate::resolver;
+use crate::storage::Storage;
+use anyhow::Result;
+use clap::ValueEnum;
+use std::env;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+/// Returns the `bin` directory that should be on PATH for `cwd`: the version
+/// requested by `CMVM_VERSION` or a `.cmake-version` file when it is
+/// installed, otherwise the global `current` symlink.
+pub fn resolve_bin_dir(cwd: &Path, storage: &impl Storage) -> Result<PathBuf> {
+    if let Some((request, source)) = project::get_requested_version(cwd)? {
+        match resolver::resolve_installed(&request, storage)? {
+            Some(version) => {
+                if let Some((version_dir, _)) = storage.find_version_dir(&version.get_tag_name())? {
+                    return Ok(version_dir.join("bin"));
+                }
+            }
+            None => eprintln!(
+                "[cmvm] Version {} requested by {} is not installed. Use `cmvm install {}` to install it.",
+                request, source, request
+            ),
+        }
+    }
+
+    Ok(storage.get_current_version_dir()?.join("bin"))
+}
+
+/// Returns the current PATH with `bin_dir` in front of it.
+pub fn prepend_to_path(bin_dir: &Path) -> Result<OsString> {
+    let current_path = env::var_os("PATH").unwrap_or_default();
+    let paths = std::iter::once(bin_dir.to_path_buf()).chain(env::split_paths(&current_path));
+    Ok(env::join_paths(paths)?)
+}
+
+/// Returns a hook that re-runs `cmvm bin-path` whenever the working directory
+/// changes and swaps the previous cmake `bin` directory on PATH for the new one.
+pub fn hook(shell: Shell) -> String {
+    match shell {
+        Shell::Bash => BASH_HOOK.to_string(),
+        Shell::Zsh => ZSH_HOOK.to_string(),
+        Shell::Fish => FISH_HOOK.to_string(),
+    }
+}
+
+/// Returns the lines that register a multishell session link and put its
+/// `bin` directory on PATH.
+pub fn multishell_env(shell: Shell, session_link: &Path) -> String {
+    let session_link = session_link.display();
+    match shell {
+        Shell::Bash | Shell::Zsh => format!(
+            "export {}=\"{}\"\nexport PATH=\"{}/bin:$PATH\"\n",
+            MULTISHELL_ENV_VAR, session_link, session_link
+        ),
+        Shell::Fish => format!(
+            "set -gx {} \"{}\"\nset -gx PATH \"{}/bin\" $PATH\n",
+            MULTISHELL_ENV_VAR, session_link, session_link
+        ),
+    }
+}
+
+const BASH_HOOK: &str = r#"__cmvm_use() {
+  local bin
+  bin="$(command cmvm bin-path 2>/dev/null)" || return
+  if [ -n "$__CMVM_BIN" ]; then
+    PATH=":$PATH:"
+    PATH="${PATH//:$__CMVM_BIN:/:}"
+    PATH="${PATH#:}"
+    PATH="${PATH%:}"
+  fi
+  export __CMVM_BIN="$bin"
+  export PATH="$bin:$PATH"
+}
+
+__cmvm_hook() {
+  if [ "$__CMVM_LAST_PWD" != "$PWD" ]; then
+    __CMVM_LAST_PWD="$PWD"
+    __cmvm_use
+  fi
+}
+
+if [[ ";${PROMPT_COMMAND:-};" != *";__cmvm_hook;"* ]]; then
+  PROMPT_COMMAND="__cmvm_hook${PROMPT_COMMAND:+;$PROMPT_COMMAND}"
+fi
+__cmvm_hook
+"#;
+
+const ZSH_HOOK: &str = r#"__cmvm_use() {
+  local bin
+  bin="$(command cmvm bin-path 2>/dev/null)" || return
+  if [[ -n "$__CMVM_BIN" ]]; then
+    path=("${(@)path:#$__CMVM_BIN}")
+  fi
+  export __CMVM_BIN="$bin"
+  export PATH="$bin:$PATH"
+}
+
+autoload -U add-zsh-hook
+add-zsh-hook chpwd __cmvm_use
+__cmvm_use
+"#;
+
+const FISH_HOOK: &str = r#"function __cmvm_use --on-variable PWD
+    set -l bin (command cmvm bin-path 2>/dev/null); or return
+    if set -q __CMVM_BIN; and set -l index (contains -i -- $__CMVM_BIN $PATH)
+        set -e PATH[$index]
+    end
+    set -gx __CMVM_BIN $bin
+    set -gx PATH $bin $PATH
+end
+
+__cmvm_use
+"#;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::test_support::MockStorage;
+    use pretty_assertions::assert_eq;
+    use std::fs;
+
+    #[test]
+    fn test_hook_registers_directory_change_handler() {
+        assert!(hook(Shell::Bash).contains("PROMPT_COMMAND"));
+        assert!(hook(Shell::Zsh).contains("add-zsh-hook chpwd __cmvm_use"));
+        assert!(hook(Shell::Fish).contains("--on-variable PWD"));
+    }
+
+    #[test]
+    fn test_prepend_to_path_puts_bin_dir_first() {
+        let bin_dir = Path::new("/opt/cmvm/versions/3.28.1/bin");
+        let path = prepend_to_path(bin_dir).unwrap();
+        let first = env::split_paths(&path).next();
+        assert_eq!(first, Some(bin_dir.to_path_buf()));
+    }
+
+    #[test]
+    fn test_multishell_env_exports_session_link() {
+        let session_link = Path::new("/tmp/cmvm/multishells/42_1");
+        assert_eq!(
+            multishell_env(Shell::Zsh, session_link),
+            "export CMVM_MULTISHELL_PATH=\"/tmp/cmvm/multishells/42_1\"\nexport PATH=\"/tmp/cmvm/multishells/42_1/bin:$PATH\"\n"
+        );
+        assert!(multishell_env(Shell::Fish, session_link)
+            .starts_with("set -gx CMVM_MULTISHELL_PATH \"/tmp/cmvm/multishells/42_1\""));
+    }
+
+    #[test]
+    fn test_resolve_bin_dir_uses_project_file() {
+        let cache_dir = env::temp_dir().join("cmvm_test_shell_bin_dir");
+        let _ = fs::remove_dir_all(&cache_dir);
+        fs::create_dir_all(cache_dir.join("versions").join("3.28.1")).unwrap();
+        let project_dir = cache_dir.join("project");
+        fs::create_dir_all(&project_dir).unwrap();
+        fs::write(project_dir.join(".cmake-version"), "3.28\n").unwrap();
+        let storage = MockStorage::new(&cache_dir);
+
+        let bin_dir = resolve_bin_dir(&project_dir, &storage).unwrap();
+        let global_bin_dir = resolve_bin_dir(&cache_dir, &storage).unwrap();
+        let _ = fs::remove_dir_all(&cache_dir);
+
+        assert_eq!(
+            bin_dir,
+            cache_dir.join("versions").join("3.28.1").join("bin")
+        );
+        assert_eq!(global_bin_dir, cache_dir.join("current").join("bin"));
+    }
+}
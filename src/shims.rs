@@ -0,0 +1,92 @@
+use crate::cache;
+use crate::constants::SHIM_NAMES;
+use crate::shell;
+use crate::storage::Storage;
+use anyhow::{bail, Result};
+use std::ffi::OsString;
+use std::fs;
+use std::io::Write;
+use std::os::unix::fs::PermissionsExt;
+use std::os::unix::process::CommandExt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Writes one dispatcher script per cmake tool into the shims dir. Each script
+/// hands over to `cmvm shim <tool>`, which picks the version at invocation time.
+pub fn rehash(cmvm: &Path, storage: &impl Storage) -> Result<Vec<PathBuf>> {
+    let shims_dir = storage.get_shims_dir()?;
+    fs::create_dir_all(&shims_dir)?;
+
+    let mut shims = Vec::new();
+    for name in SHIM_NAMES {
+        let shim_path = shims_dir.join(name);
+        let mut file = cache::create_file(&shim_path)?;
+        file.write_all(shim_script(cmvm, name).as_bytes())?;
+        fs::set_permissions(&shim_path, fs::Permissions::from_mode(0o755))?;
+        shims.push(shim_path);
+    }
+    Ok(shims)
+}
+
+fn shim_script(cmvm: &Path, name: &str) -> String {
+    format!(
+        "#!/bin/sh\n# generated by `cmvm rehash`, do not edit\nexec \"{}\" shim {} \"$@\"\n",
+        cmvm.display(),
+        name
+    )
+}
+
+/// Replaces the current process with `name` from the version resolved for
+/// `cwd`: `CMVM_VERSION`, then `.cmake-version`, then the global default.
+pub fn exec(name: &str, args: &[OsString], cwd: &Path, storage: &impl Storage) -> Result<()> {
+    if !SHIM_NAMES.contains(&name) {
+        bail!("[cmvm] {} is not a cmake tool.", name);
+    }
+
+    let bin_dir = shell::resolve_bin_dir(cwd, storage)?;
+    let program = bin_dir.join(name);
+    if !program.exists() {
+        bail!(
+            "[cmvm] {} not found in {}. Use `cmvm use <version>` to set a version.",
+            name,
+            bin_dir.display()
+        );
+    }
+
+    let error = Command::new(&program).args(args).exec();
+    bail!("[cmvm] Failed to run {}: {}", program.display(), error)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::test_support::MockStorage;
+    use pretty_assertions::assert_eq;
+    use std::env;
+
+    #[test]
+    fn test_rehash_writes_executable_shims() {
+        let cache_dir = env::temp_dir().join("cmvm_test_shims_rehash");
+        let _ = fs::remove_dir_all(&cache_dir);
+        fs::create_dir_all(&cache_dir).unwrap();
+        let storage = MockStorage::new(&cache_dir);
+
+        let shims = rehash(Path::new("/usr/local/bin/cmvm"), &storage).unwrap();
+        let cmake_shim = fs::read_to_string(cache_dir.join("shims").join("cmake")).unwrap();
+        let mode = fs::metadata(cache_dir.join("shims").join("ctest"))
+            .unwrap()
+            .permissions()
+            .mode();
+        let _ = fs::remove_dir_all(&cache_dir);
+
+        assert_eq!(shims.len(), SHIM_NAMES.len());
+        assert!(cmake_shim.contains("exec \"/usr/local/bin/cmvm\" shim cmake \"$@\""));
+        assert_eq!(mode & 0o111, 0o111);
+    }
+
+    #[test]
+    fn test_exec_rejects_unknown_tool() {
+        let storage = MockStorage::new(&env::temp_dir().join("cmvm_test_shims_unknown"));
+        assert!(exec("make", &[], &env::temp_dir(), &storage).is_err());
+    }
+}
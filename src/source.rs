@@ -0,0 +1,344 @@
+use crate::config::Config;
+use crate::constants::{CMAKE_GIT_URL, FROM_SOURCE_STAGING_DIR, LOGS_DIR_NAME};
+use crate::error::CmvmError;
+use crate::resolver::VersionRequest;
+use crate::storage::Storage;
+use crate::versions::Version;
+use crate::{cache, package};
+use anyhow::{anyhow, bail, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+/// Where `cmvm install --from-source` takes the CMake sources from.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Source {
+    /// An unpacked source tree with a `bootstrap` script.
+    Directory(PathBuf),
+    /// A source tarball such as `cmake-3.28.1.tar.gz`.
+    Tarball(PathBuf),
+    /// A release from the cached index; its source tarball is downloaded.
+    Release(Version),
+    /// A branch, tag or commit of the CMake git repository.
+    GitRef(String),
+}
+
+impl Source {
+    /// Reads `value` as a path first, then as a version known to the
+    /// release index, and otherwise as a git ref.
+    pub fn parse(value: &str, storage: &impl Storage) -> Result<Source> {
+        let path = PathBuf::from(value);
+        if path.is_dir() {
+            return Ok(Source::Directory(path));
+        }
+        if path.is_file() {
+            if !value.ends_with(".tar.gz") {
+                bail!("[cmvm] {} is not a .tar.gz source tarball.", value);
+            }
+            return Ok(Source::Tarball(path));
+        }
+        // every release has a source tarball, so no platform asset is needed
+        let release = VersionRequest::parse(value)
+            .ok()
+            .zip(Version::all_from_cache(storage).ok())
+            .and_then(|(request, versions)| request.best_match(versions));
+        match release {
+            Some(version) => Ok(Source::Release(version)),
+            None => Ok(Source::GitRef(value.to_string())),
+        }
+    }
+}
+
+/// Options passed through to CMake's build.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BuildOptions {
+    pub jobs: usize,
+    /// Extra arguments for `bootstrap`, e.g. `--no-qt-gui`.
+    pub bootstrap_args: Vec<String>,
+}
+
+/// Builds CMake from `source` with `bootstrap` and `make install` into a
+/// staging prefix, then installs the prefix under `versions/<name>`. Without
+/// a name the version reported by the built `cmake --version` is used.
+/// Returns the name it was installed under.
+pub fn install(
+    source: &Source,
+    name: Option<&str>,
+    options: &BuildOptions,
+    config: &Config,
+    storage: &impl Storage,
+) -> Result<String> {
+    let staging_dir = storage.get_cache_dir()?.join(FROM_SOURCE_STAGING_DIR);
+    if staging_dir.exists() {
+        cache::delete(&staging_dir)?;
+    }
+    cache::create_dir(&staging_dir)?;
+
+    let log_path = get_log_path(source, storage)?;
+    eprintln!("[cmvm] Build log: {}.", log_path.display());
+
+    let result =
+        build(source, &staging_dir, &log_path, options, config, storage).and_then(|prefix| {
+            let tag_name = match name {
+                Some(name) => name.to_string(),
+                None => package::version_from_cmake(&prefix)
+                    .ok_or_else(|| anyhow!("cannot tell the version of the built cmake."))?,
+            };
+            if storage.get_versions_dir()?.join(&tag_name).exists() {
+                bail!("version {} is already installed.", tag_name);
+            }
+            package::copy(&tag_name, &prefix, storage)?;
+            Ok(tag_name)
+        });
+
+    cache::delete(&staging_dir)?;
+    result.map_err(|e| match e.downcast::<CmvmError>() {
+        Ok(e) => e.into(),
+        Err(e) => CmvmError::InstallFailed {
+            version: name.unwrap_or(&describe(source)).to_string(),
+            reason: e.to_string(),
+        }
+        .into(),
+    })
+}
+
+/// Fetches the sources into `staging_dir` when needed, builds them and
+/// returns the install prefix.
+fn build(
+    source: &Source,
+    staging_dir: &Path,
+    log_path: &Path,
+    options: &BuildOptions,
+    config: &Config,
+    storage: &impl Storage,
+) -> Result<PathBuf> {
+    let source_dir = match source {
+        Source::Directory(path) => fs::canonicalize(path)?,
+        Source::Tarball(path) => {
+            let asset = package::local_asset(path)?;
+            fs::copy(path, staging_dir.join(&asset.name))?;
+            package::uncompress(FROM_SOURCE_STAGING_DIR, &asset, storage)?;
+            find_source_dir(staging_dir)?
+        }
+        Source::Release(version) => {
+            let asset_name = format!("cmake-{}.tar.gz", version.get_tag_name());
+            let Some(asset) = version.assets.iter().find(|a| a.name == asset_name) else {
+                bail!("release {} has no source tarball.", version.get_tag_name());
+            };
+            package::download(FROM_SOURCE_STAGING_DIR, asset, config, storage)?;
+            package::uncompress(FROM_SOURCE_STAGING_DIR, asset, storage)?;
+            find_source_dir(staging_dir)?
+        }
+        Source::GitRef(git_ref) => {
+            if config.offline() {
+                bail!(
+                    "[cmvm] Cannot fetch {} from {} in offline mode.",
+                    git_ref,
+                    CMAKE_GIT_URL
+                );
+            }
+            let source_dir = staging_dir.join("src");
+            cache::create_dir(&source_dir)?;
+            eprintln!("[cmvm] Fetching {} from {}.", git_ref, CMAKE_GIT_URL);
+            for args in [
+                vec!["init", "--quiet"],
+                vec!["fetch", "--depth", "1", CMAKE_GIT_URL, git_ref.as_str()],
+                vec!["checkout", "--quiet", "FETCH_HEAD"],
+            ] {
+                run(
+                    Command::new("git").args(&args).current_dir(&source_dir),
+                    log_path,
+                )?;
+            }
+            source_dir
+        }
+    };
+
+    if !source_dir.join("bootstrap").is_file() {
+        bail!("no bootstrap script found in {}.", source_dir.display());
+    }
+
+    let build_dir = staging_dir.join("build");
+    let prefix = staging_dir.join("prefix");
+    cache::create_dir(&build_dir)?;
+
+    eprintln!("[cmvm] Running bootstrap.");
+    run(
+        Command::new(source_dir.join("bootstrap"))
+            .arg(format!("--prefix={}", prefix.display()))
+            .arg(format!("--parallel={}", options.jobs))
+            .args(&options.bootstrap_args)
+            .current_dir(&build_dir),
+        log_path,
+    )?;
+
+    eprintln!("[cmvm] Building with {} jobs.", options.jobs);
+    run(
+        Command::new("make")
+            .arg(format!("-j{}", options.jobs))
+            .current_dir(&build_dir),
+        log_path,
+    )?;
+
+    eprintln!("[cmvm] Installing into the staging prefix.");
+    run(
+        Command::new("make").arg("install").current_dir(&build_dir),
+        log_path,
+    )?;
+
+    Ok(prefix)
+}
+
+/// Runs `command` with its output appended to the build log.
+fn run(command: &mut Command, log_path: &Path) -> Result<()> {
+    let log = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(log_path)?;
+    let program = command.get_program().to_string_lossy().to_string();
+    let status = command
+        .stdin(Stdio::null())
+        .stdout(log.try_clone()?)
+        .stderr(log)
+        .status()
+        .map_err(|e| anyhow!("failed to run {}: {}", program, e))?;
+
+    if !status.success() {
+        bail!("{} failed, see {}.", program, log_path.display());
+    }
+    Ok(())
+}
+
+/// The single directory a source tarball unpacks to.
+fn find_source_dir(staging_dir: &Path) -> Result<PathBuf> {
+    cache::ls(staging_dir)?
+        .into_iter()
+        .find(|path| path.is_dir())
+        .ok_or_else(|| anyhow!("source tarball has no top-level directory."))
+}
+
+/// `<cache_dir>/logs/build-<source>.log`, started afresh for every build.
+fn get_log_path(source: &Source, storage: &impl Storage) -> Result<PathBuf> {
+    let logs_dir = storage.get_cache_dir()?.join(LOGS_DIR_NAME);
+    fs::create_dir_all(&logs_dir)?;
+
+    let file_name: String = describe(source)
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '.' || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    let log_path = logs_dir.join(format!("build-{}.log", file_name));
+    if log_path.exists() {
+        fs::remove_file(&log_path)?;
+    }
+    Ok(log_path)
+}
+
+fn describe(source: &Source) -> String {
+    match source {
+        Source::Directory(path) | Source::Tarball(path) => path
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default(),
+        Source::Release(version) => version.get_tag_name(),
+        Source::GitRef(git_ref) => git_ref.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::test_support::storage_in;
+    use pretty_assertions::assert_eq;
+
+    use std::os::unix::fs::PermissionsExt;
+
+    /// A source tree whose `bootstrap` writes a Makefile that installs a
+    /// `bin/cmake` reporting version 3.99.0.
+    fn write_fake_source(dir: &Path) {
+        fs::create_dir_all(dir).unwrap();
+        let bootstrap = dir.join("bootstrap");
+        fs::write(
+            &bootstrap,
+            r##"#!/bin/sh
+prefix="${1#--prefix=}"
+printf 'all:\n\t@echo building\ninstall:\n\tmkdir -p %s/bin\n\tprintf "#!/bin/sh\\necho cmake version 3.99.0\\n" > %s/bin/cmake\n\tchmod +x %s/bin/cmake\n' "$prefix" "$prefix" "$prefix" > Makefile
+"##,
+        )
+        .unwrap();
+        fs::set_permissions(&bootstrap, fs::Permissions::from_mode(0o755)).unwrap();
+    }
+
+    #[test]
+    fn test_parse_prefers_paths_then_falls_back_to_git_ref() {
+        let storage = storage_in("cmvm_test_source_parse");
+        let source_dir = storage.root.join("cmake-src");
+        fs::create_dir_all(&source_dir).unwrap();
+
+        let directory = Source::parse(&source_dir.to_string_lossy(), &storage).unwrap();
+        let git_ref = Source::parse("master", &storage).unwrap();
+        cache::delete(&storage.root).ok();
+
+        assert_eq!(directory, Source::Directory(source_dir));
+        assert_eq!(git_ref, Source::GitRef("master".to_string()));
+    }
+
+    #[test]
+    fn test_install_builds_directory_into_versions() {
+        let storage = storage_in("cmvm_test_source_install");
+        let source_dir = storage.root.join("cmake-src");
+        write_fake_source(&source_dir);
+        let options = BuildOptions {
+            jobs: 1,
+            bootstrap_args: vec![],
+        };
+
+        let tag_name = install(
+            &Source::Directory(source_dir),
+            None,
+            &options,
+            &Config::default(),
+            &storage,
+        )
+        .unwrap();
+        let installed = storage.root.join("versions/3.99.0/bin/cmake").exists();
+        let log_exists = storage.cache_dir.join("logs/build-cmake-src.log").exists();
+        let staging_left = storage.cache_dir.join(FROM_SOURCE_STAGING_DIR).exists();
+        cache::delete(&storage.root).ok();
+
+        assert_eq!(tag_name, "3.99.0");
+        assert!(installed);
+        assert!(log_exists);
+        assert!(!staging_left);
+    }
+
+    #[test]
+    fn test_install_fails_without_bootstrap() {
+        let storage = storage_in("cmvm_test_source_no_bootstrap");
+        let source_dir = storage.root.join("empty-src");
+        fs::create_dir_all(&source_dir).unwrap();
+        let options = BuildOptions {
+            jobs: 1,
+            bootstrap_args: vec![],
+        };
+
+        let result = install(
+            &Source::Directory(source_dir),
+            None,
+            &options,
+            &Config::default(),
+            &storage,
+        );
+        cache::delete(&storage.root).ok();
+
+        assert!(matches!(
+            result.unwrap_err().downcast_ref::<CmvmError>(),
+            Some(CmvmError::InstallFailed { .. })
+        ));
+    }
+}
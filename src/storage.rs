@@ -1,12 +1,145 @@
-use anyhow::{anyhow, Result};
+use crate::config::Config;
+use crate::constants::{
+    ALIASES_DIR_NAME, CACHE_DIR_NAME, CONFIG_FILE_NAME, MULTISHELLS_DIR_NAME, MULTISHELL_ENV_VAR,
+    SHIMS_DIR_NAME,
+};
+use anyhow::{anyhow, bail, Result};
 use directories::ProjectDirs;
-use std::path::PathBuf;
+use serde::Serialize;
+use std::env;
+use std::ffi::CString;
+use std::os::unix::ffi::OsStrExt;
+use std::path::{self, Path, PathBuf};
 
 pub(crate) trait Storage {
     fn get_cache_dir(&self) -> Result<PathBuf>;
     fn get_data_dir(&self) -> Result<PathBuf>;
     fn get_current_version_dir(&self) -> Result<PathBuf>;
     fn get_versions_dir(&self) -> Result<PathBuf>;
+
+    fn get_multishells_dir(&self) -> Result<PathBuf> {
+        Ok(self.get_cache_dir()?.join(MULTISHELLS_DIR_NAME))
+    }
+
+    fn get_shims_dir(&self) -> Result<PathBuf> {
+        Ok(self.get_data_dir()?.join(SHIMS_DIR_NAME))
+    }
+
+    fn get_aliases_dir(&self) -> Result<PathBuf> {
+        Ok(self.get_data_dir()?.join(ALIASES_DIR_NAME))
+    }
+
+    /// The shared, usually read-only, versions directory layered under
+    /// `get_versions_dir`.
+    fn get_system_versions_dir(&self) -> Result<Option<PathBuf>> {
+        Ok(None)
+    }
+
+    /// Finds the installed version `tag_name`, in the user's versions
+    /// directory first and then in the system store.
+    fn find_version_dir(&self, tag_name: &str) -> Result<Option<(PathBuf, Store)>> {
+        let user_path = self.get_versions_dir()?.join(tag_name);
+        if user_path.is_dir() {
+            return Ok(Some((user_path, Store::User)));
+        }
+        Ok(self
+            .get_system_versions_dir()?
+            .map(|dir| dir.join(tag_name))
+            .filter(|path| path.is_dir())
+            .map(|path| (path, Store::System)))
+    }
+}
+
+/// Where an installed version lives.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum Store {
+    User,
+    System,
+}
+
+impl<S: Storage + ?Sized> Storage for Box<S> {
+    fn get_cache_dir(&self) -> Result<PathBuf> {
+        (**self).get_cache_dir()
+    }
+
+    fn get_data_dir(&self) -> Result<PathBuf> {
+        (**self).get_data_dir()
+    }
+
+    fn get_current_version_dir(&self) -> Result<PathBuf> {
+        (**self).get_current_version_dir()
+    }
+
+    fn get_versions_dir(&self) -> Result<PathBuf> {
+        (**self).get_versions_dir()
+    }
+
+    fn get_multishells_dir(&self) -> Result<PathBuf> {
+        (**self).get_multishells_dir()
+    }
+
+    fn get_shims_dir(&self) -> Result<PathBuf> {
+        (**self).get_shims_dir()
+    }
+
+    fn get_aliases_dir(&self) -> Result<PathBuf> {
+        (**self).get_aliases_dir()
+    }
+
+    fn get_system_versions_dir(&self) -> Result<Option<PathBuf>> {
+        (**self).get_system_versions_dir()
+    }
+}
+
+/// The storage selected by the `root`, `cache_dir`, `versions_dir` and
+/// `system_versions_dir` settings: a `HomeStorage` when `root` is set, the
+/// OS-standard directories otherwise, with the other directories overridden
+/// on top. Relative paths are taken from the current directory.
+pub(crate) fn from_config(config: &Config) -> Result<Box<dyn Storage>> {
+    let base: Box<dyn Storage> = match &config.root {
+        Some(root) => Box::new(HomeStorage::new(&path::absolute(root)?)),
+        None => Box::new(StorageImpl::default()),
+    };
+    Ok(Box::new(OverrideStorage {
+        base,
+        cache_dir: config
+            .cache_dir
+            .as_deref()
+            .map(path::absolute)
+            .transpose()?,
+        versions_dir: config
+            .versions_dir
+            .as_deref()
+            .map(path::absolute)
+            .transpose()?,
+        system_versions_dir: Some(path::absolute(config.system_versions_dir())?),
+    }))
+}
+
+/// Fails unless the current user may write to `dir`, or to the closest
+/// existing parent it would be created in.
+pub fn ensure_writable(dir: &Path) -> Result<()> {
+    let existing = dir
+        .ancestors()
+        .find(|path| path.exists())
+        .unwrap_or(Path::new("/"));
+    let path = CString::new(existing.as_os_str().as_bytes())?;
+    if unsafe { libc::access(path.as_ptr(), libc::W_OK) } != 0 {
+        bail!(
+            "[cmvm] {} is not writable. Changing the system store needs administrator privileges, e.g. `sudo`.",
+            dir.display()
+        );
+    }
+    Ok(())
+}
+
+/// `config.toml` in the OS-standard config directory.
+pub fn user_config_file() -> Result<PathBuf> {
+    Ok(StorageImpl::default()
+        .get_project_dirs()?
+        .config_dir()
+        .join(CONFIG_FILE_NAME))
 }
 #[derive(Debug, Clone)]
 pub struct StorageImpl {
@@ -39,6 +172,10 @@ impl Storage for StorageImpl {
     }
 
     fn get_current_version_dir(&self) -> Result<PathBuf> {
+        // a shell started with `cmvm env --multishell` has its own `current` link
+        if let Some(session_link) = env::var_os(MULTISHELL_ENV_VAR).filter(|p| !p.is_empty()) {
+            return Ok(PathBuf::from(session_link));
+        }
         Ok(self.get_project_dirs()?.data_dir().join("current"))
     }
 
@@ -46,3 +183,240 @@ impl Storage for StorageImpl {
         Ok(self.get_project_dirs()?.data_dir().join("versions"))
     }
 }
+
+/// Keeps everything under a single directory:
+///
+/// ```text
+/// <root>/
+///   versions/, current, aliases/, shims/
+///   cache/
+/// ```
+#[derive(Debug, Clone)]
+pub struct HomeStorage {
+    root: PathBuf,
+}
+
+impl HomeStorage {
+    pub fn new(root: &Path) -> Self {
+        Self {
+            root: root.to_path_buf(),
+        }
+    }
+}
+
+impl Storage for HomeStorage {
+    fn get_cache_dir(&self) -> Result<PathBuf> {
+        Ok(self.root.join(CACHE_DIR_NAME))
+    }
+
+    fn get_data_dir(&self) -> Result<PathBuf> {
+        Ok(self.root.clone())
+    }
+
+    fn get_current_version_dir(&self) -> Result<PathBuf> {
+        if let Some(session_link) = env::var_os(MULTISHELL_ENV_VAR).filter(|p| !p.is_empty()) {
+            return Ok(PathBuf::from(session_link));
+        }
+        Ok(self.root.join("current"))
+    }
+
+    fn get_versions_dir(&self) -> Result<PathBuf> {
+        Ok(self.root.join("versions"))
+    }
+}
+
+/// `base` with its cache and/or versions dir moved elsewhere, e.g. versions
+/// on a shared volume or the cache in a CI cache dir, and the system store
+/// layered under it.
+struct OverrideStorage<S: Storage> {
+    base: S,
+    cache_dir: Option<PathBuf>,
+    versions_dir: Option<PathBuf>,
+    system_versions_dir: Option<PathBuf>,
+}
+
+impl<S: Storage> Storage for OverrideStorage<S> {
+    fn get_cache_dir(&self) -> Result<PathBuf> {
+        match &self.cache_dir {
+            Some(cache_dir) => Ok(cache_dir.clone()),
+            None => self.base.get_cache_dir(),
+        }
+    }
+
+    fn get_data_dir(&self) -> Result<PathBuf> {
+        self.base.get_data_dir()
+    }
+
+    fn get_current_version_dir(&self) -> Result<PathBuf> {
+        self.base.get_current_version_dir()
+    }
+
+    fn get_versions_dir(&self) -> Result<PathBuf> {
+        match &self.versions_dir {
+            Some(versions_dir) => Ok(versions_dir.clone()),
+            None => self.base.get_versions_dir(),
+        }
+    }
+
+    fn get_system_versions_dir(&self) -> Result<Option<PathBuf>> {
+        match &self.system_versions_dir {
+            Some(dir) => Ok(Some(dir.clone())),
+            None => self.base.get_system_versions_dir(),
+        }
+    }
+}
+
+/// The `Storage` the unit tests run against.
+#[cfg(test)]
+pub(crate) mod test_support {
+    use super::Storage;
+    use anyhow::Result;
+    use std::env;
+    use std::fs;
+    use std::path::{Path, PathBuf};
+
+    /// Keeps versions, `current`, aliases and shims under `root`, the cache
+    /// in `cache_dir` and the system store in `root/system`.
+    pub struct MockStorage {
+        pub root: PathBuf,
+        pub cache_dir: PathBuf,
+        /// Stands in for `CMVM_MULTISHELL_PATH`: the `current` link of a
+        /// `--multishell` session.
+        pub session_link: Option<PathBuf>,
+    }
+
+    impl MockStorage {
+        /// Everything under `root`, the cache included.
+        pub fn new(root: &Path) -> Self {
+            Self {
+                root: root.to_path_buf(),
+                cache_dir: root.to_path_buf(),
+                session_link: None,
+            }
+        }
+    }
+
+    impl Storage for MockStorage {
+        fn get_cache_dir(&self) -> Result<PathBuf> {
+            Ok(self.cache_dir.clone())
+        }
+        fn get_data_dir(&self) -> Result<PathBuf> {
+            Ok(self.root.clone())
+        }
+        fn get_current_version_dir(&self) -> Result<PathBuf> {
+            match &self.session_link {
+                Some(session_link) => Ok(session_link.clone()),
+                None => Ok(self.root.join("current")),
+            }
+        }
+        fn get_versions_dir(&self) -> Result<PathBuf> {
+            Ok(self.root.join("versions"))
+        }
+        fn get_system_versions_dir(&self) -> Result<Option<PathBuf>> {
+            Ok(Some(self.root.join("system")))
+        }
+    }
+
+    /// An empty `<tmp>/<name>` with `versions/` and the cache kept apart in
+    /// `cache/`, for tests that look at what is left in the cache.
+    pub fn storage_in(name: &str) -> MockStorage {
+        let root = env::temp_dir().join(name);
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("cache")).unwrap();
+        fs::create_dir_all(root.join("versions")).unwrap();
+        MockStorage {
+            cache_dir: root.join("cache"),
+            root,
+            session_link: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_home_storage_roots_everything() {
+        let config = Config {
+            root: Some(PathBuf::from("/srv/cmvm")),
+            ..Config::default()
+        };
+        let storage = from_config(&config).unwrap();
+
+        assert_eq!(storage.get_data_dir().unwrap(), PathBuf::from("/srv/cmvm"));
+        assert_eq!(
+            storage.get_cache_dir().unwrap(),
+            PathBuf::from("/srv/cmvm/cache")
+        );
+        assert_eq!(
+            storage.get_versions_dir().unwrap(),
+            PathBuf::from("/srv/cmvm/versions")
+        );
+        assert_eq!(
+            storage.get_aliases_dir().unwrap(),
+            PathBuf::from("/srv/cmvm/aliases")
+        );
+    }
+
+    #[test]
+    fn test_cache_and_versions_dirs_override_root() {
+        let config = Config {
+            root: Some(PathBuf::from("/srv/cmvm")),
+            cache_dir: Some(PathBuf::from("/ci/cache")),
+            versions_dir: Some(PathBuf::from("/mnt/shared/cmake")),
+            ..Config::default()
+        };
+        let storage = from_config(&config).unwrap();
+
+        assert_eq!(storage.get_data_dir().unwrap(), PathBuf::from("/srv/cmvm"));
+        assert_eq!(storage.get_cache_dir().unwrap(), PathBuf::from("/ci/cache"));
+        assert_eq!(
+            storage.get_multishells_dir().unwrap(),
+            PathBuf::from("/ci/cache/multishells")
+        );
+        assert_eq!(
+            storage.get_versions_dir().unwrap(),
+            PathBuf::from("/mnt/shared/cmake")
+        );
+    }
+
+    #[test]
+    fn test_find_version_dir_prefers_user_store() {
+        let root = env::temp_dir().join("cmvm_test_storage_system_store");
+        let _ = std::fs::remove_dir_all(&root);
+        let config = Config {
+            root: Some(root.join("home")),
+            system_versions_dir: Some(root.join("system")),
+            ..Config::default()
+        };
+        for dir in ["home/versions/3.28.1", "system/3.28.1", "system/3.20.6"] {
+            std::fs::create_dir_all(root.join(dir)).unwrap();
+        }
+        let storage = from_config(&config).unwrap();
+
+        let user = storage.find_version_dir("3.28.1").unwrap();
+        let system = storage.find_version_dir("3.20.6").unwrap();
+        let missing = storage.find_version_dir("3.10.0").unwrap();
+        let _ = std::fs::remove_dir_all(&root);
+
+        assert_eq!(user, Some((root.join("home/versions/3.28.1"), Store::User)));
+        assert_eq!(system, Some((root.join("system/3.20.6"), Store::System)));
+        assert_eq!(missing, None);
+    }
+
+    #[test]
+    fn test_relative_root_is_made_absolute() {
+        let config = Config {
+            root: Some(PathBuf::from("cmvm-home")),
+            ..Config::default()
+        };
+        let storage = from_config(&config).unwrap();
+
+        assert_eq!(
+            storage.get_data_dir().unwrap(),
+            env::current_dir().unwrap().join("cmvm-home")
+        );
+    }
+}
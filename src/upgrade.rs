@@ -0,0 +1,301 @@
+use crate::config::Config;
+use crate::error::CmvmError;
+use crate::resolver::VersionRequest;
+use crate::storage::{Storage, Store};
+use crate::versions::Version;
+use crate::{aliases, package, platform, releases};
+use anyhow::{bail, Result};
+use serde::Serialize;
+
+/// An installed version with newer releases in the cached index.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Outdated {
+    pub version: String,
+    /// Newest release with the same major and minor version.
+    pub patch: Option<String>,
+    /// Newest release with the same major version, when its minor is newer.
+    pub minor: Option<String>,
+}
+
+/// What `apply` did to replace a version.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Upgrade {
+    pub from: String,
+    pub to: String,
+    /// Whether `to` had to be installed first.
+    pub installed: bool,
+    pub current_moved: bool,
+    /// Aliases moved from `from` to `to`.
+    pub aliases: Vec<String>,
+    pub removed_old: bool,
+}
+
+/// Returns the newest release in `remote` that supersedes `installed`:
+/// within the same minor series, or within the same major one with `minor`.
+pub fn find_upgrade(installed: &Version, remote: &[Version], minor: bool) -> Option<Version> {
+    let major = installed.major.unwrap_or(0);
+    let request = match minor {
+        true => VersionRequest::Partial(vec![major]),
+        false => VersionRequest::Partial(vec![major, installed.minor.unwrap_or(0)]),
+    };
+    request
+        .best_match(remote.to_vec())
+        .filter(|candidate| candidate > installed)
+}
+
+/// Compares every version in the user's `versions/` against `remote`, the
+/// releases installable on this platform. Only final releases are
+/// suggested, so an installed prerelease is reported once its final release
+/// is out.
+pub fn outdated(remote: &[Version], storage: &impl Storage) -> Result<Vec<Outdated>> {
+    Ok(Version::all_installed_in(Store::User, storage)?
+        .iter()
+        .filter_map(|installed| {
+            let patch = find_upgrade(installed, remote, false);
+            let minor = find_upgrade(installed, remote, true)
+                .filter(|candidate| candidate.minor != installed.minor);
+            if patch.is_none() && minor.is_none() {
+                return None;
+            }
+            Some(Outdated {
+                version: installed.get_tag_name(),
+                patch: patch.map(|v| v.get_tag_name()),
+                minor: minor.map(|v| v.get_tag_name()),
+            })
+        })
+        .collect())
+}
+
+/// Replaces `old` with `new`: installs `new` when needed, moves `current`
+/// and every alias of `old` over to it and, with `remove_old`, uninstalls
+/// `old` afterwards.
+pub fn apply(
+    old: &str,
+    new: &Version,
+    remove_old: bool,
+    config: &Config,
+    storage: &impl Storage,
+) -> Result<Upgrade> {
+    let tag_name = new.get_tag_name();
+    let old_dir = storage.find_version_dir(old)?.map(|(path, _)| path);
+
+    let installed = storage.find_version_dir(&tag_name)?.is_none();
+    if installed {
+        if !platform::is_supported_platform() {
+            bail!(CmvmError::UnsupportedPlatform);
+        }
+        package::get_cmake_release(new, config, storage)?;
+    }
+
+    let current_moved =
+        old_dir.is_some() && storage.get_current_version_dir()?.read_link().ok() == old_dir;
+    if current_moved {
+        new.clone().r#use(storage)?;
+    }
+
+    let moved_aliases = aliases::for_version(old, storage)?;
+    for alias in &moved_aliases {
+        aliases::set(alias, &tag_name, storage)?;
+    }
+
+    // `old` can already be the newest release when it is asked for by name
+    let remove_old = remove_old && old != tag_name;
+    if remove_old {
+        releases::delete_cache_release(old, storage)?;
+    }
+    Ok(Upgrade {
+        from: old.to_string(),
+        to: tag_name,
+        installed,
+        current_moved,
+        aliases: moved_aliases,
+        removed_old: remove_old,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cache;
+    use crate::storage::test_support::MockStorage;
+    use pretty_assertions::assert_eq;
+    use std::env;
+
+    fn versions(tags: &[&str]) -> Vec<Version> {
+        tags.iter().map(|t| Version::from_tag_name(t)).collect()
+    }
+
+    /// Releases as `releases.json` lists them, with a `v` in their tag.
+    fn released(tags: &[&str]) -> Vec<Version> {
+        tags.iter()
+            .map(|t| {
+                Version::from_raw_value(serde_json::json!({
+                    "tag_name": format!("v{}", t),
+                    "assets": []
+                }))
+                .unwrap()
+            })
+            .collect()
+    }
+
+    fn upgrade(installed: &str, minor: bool) -> Option<String> {
+        let remote = versions(&["3.20.0", "3.20.6", "3.28.1", "3.28.3", "4.0.0"]);
+        find_upgrade(&Version::from_tag_name(installed), &remote, minor).map(|v| v.get_tag_name())
+    }
+
+    #[test]
+    fn test_find_upgrade_stays_within_series() {
+        assert_eq!(upgrade("3.20.0", false), Some("3.20.6".to_string()));
+        assert_eq!(upgrade("3.20.0", true), Some("3.28.3".to_string()));
+        assert_eq!(upgrade("3.28.3", false), None);
+        assert_eq!(upgrade("3.28.3", true), None);
+    }
+
+    #[test]
+    fn test_find_upgrade_moves_prerelease_to_final() {
+        let remote = versions(&["3.29.0-rc2", "3.29.0", "3.29.1"]);
+        let upgrade = find_upgrade(&Version::from_tag_name("3.29.0-rc2"), &remote, false);
+
+        assert_eq!(upgrade.unwrap().get_tag_name(), "3.29.1");
+    }
+
+    #[test]
+    fn test_outdated_reports_patch_and_minor() {
+        let cache_dir = env::temp_dir().join("cmvm_test_outdated");
+        let _ = std::fs::remove_dir_all(&cache_dir);
+        for tag in ["3.20.0", "3.28.1", "4.0.0"] {
+            std::fs::create_dir_all(cache_dir.join("versions").join(tag)).unwrap();
+        }
+        let storage = MockStorage::new(&cache_dir);
+        let remote = versions(&["3.20.0", "3.20.6", "3.28.1", "3.28.3", "4.0.0"]);
+
+        let result = outdated(&remote, &storage).unwrap();
+        cache::delete(&cache_dir).ok();
+
+        assert_eq!(
+            result,
+            vec![
+                Outdated {
+                    version: "3.20.0".to_string(),
+                    patch: Some("3.20.6".to_string()),
+                    minor: Some("3.28.3".to_string()),
+                },
+                Outdated {
+                    version: "3.28.1".to_string(),
+                    patch: Some("3.28.3".to_string()),
+                    minor: None,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_outdated_skips_versions_matching_a_v_prefixed_release() {
+        let cache_dir = env::temp_dir().join("cmvm_test_outdated_v_prefix");
+        let _ = std::fs::remove_dir_all(&cache_dir);
+        for tag in ["3.28.1", "3.28.3"] {
+            std::fs::create_dir_all(cache_dir.join("versions").join(tag)).unwrap();
+        }
+        let storage = MockStorage::new(&cache_dir);
+        let remote = released(&["3.28.1", "3.28.3"]);
+
+        let result = outdated(&remote, &storage).unwrap();
+        cache::delete(&cache_dir).ok();
+
+        assert_eq!(
+            result,
+            vec![Outdated {
+                version: "3.28.1".to_string(),
+                patch: Some("3.28.3".to_string()),
+                minor: None,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_apply_keeps_old_when_it_is_the_newest_release() {
+        let cache_dir = env::temp_dir().join("cmvm_test_upgrade_apply_same");
+        let _ = std::fs::remove_dir_all(&cache_dir);
+        let versions_dir = cache_dir.join("versions");
+        std::fs::create_dir_all(versions_dir.join("3.28.3")).unwrap();
+        let storage = MockStorage::new(&cache_dir);
+        Version::from_tag_name("3.28.3").r#use(&storage).unwrap();
+
+        let newest = released(&["3.28.3"]).remove(0);
+        let upgrade = apply("3.28.3", &newest, true, &Config::default(), &storage).unwrap();
+        let current = cache_dir.join("current").read_link().unwrap();
+        let kept = versions_dir.join("3.28.3").exists();
+        cache::delete(&cache_dir).ok();
+
+        assert!(!upgrade.removed_old);
+        assert_eq!(current, versions_dir.join("3.28.3"));
+        assert!(kept);
+    }
+
+    #[test]
+    fn test_apply_moves_current_and_aliases() {
+        let cache_dir = env::temp_dir().join("cmvm_test_upgrade_apply");
+        let _ = std::fs::remove_dir_all(&cache_dir);
+        let versions_dir = cache_dir.join("versions");
+        for tag in ["3.20.0", "3.20.6"] {
+            std::fs::create_dir_all(versions_dir.join(tag)).unwrap();
+        }
+        let storage = MockStorage::new(&cache_dir);
+        Version::from_tag_name("3.20.0").r#use(&storage).unwrap();
+        aliases::set("legacy", "3.20.0", &storage).unwrap();
+
+        let upgrade = apply(
+            "3.20.0",
+            &Version::from_tag_name("3.20.6"),
+            true,
+            &Config::default(),
+            &storage,
+        )
+        .unwrap();
+        let current = cache_dir.join("current").read_link().unwrap();
+        let alias = aliases::get("legacy", &storage).unwrap();
+        let old_exists = versions_dir.join("3.20.0").exists();
+        cache::delete(&cache_dir).ok();
+
+        assert_eq!(
+            upgrade,
+            Upgrade {
+                from: "3.20.0".to_string(),
+                to: "3.20.6".to_string(),
+                installed: false,
+                current_moved: true,
+                aliases: vec!["legacy".to_string()],
+                removed_old: true,
+            }
+        );
+
+        assert_eq!(current, versions_dir.join("3.20.6"));
+        assert_eq!(alias, Some("3.20.6".to_string()));
+        assert!(!old_exists);
+    }
+
+    #[test]
+    fn test_apply_moves_current_out_of_system_store() {
+        let cache_dir = env::temp_dir().join("cmvm_test_upgrade_apply_system");
+        let _ = std::fs::remove_dir_all(&cache_dir);
+        std::fs::create_dir_all(cache_dir.join("system/3.20.0")).unwrap();
+        std::fs::create_dir_all(cache_dir.join("versions/3.20.6")).unwrap();
+        let storage = MockStorage::new(&cache_dir);
+        Version::from_tag_name("3.20.0").r#use(&storage).unwrap();
+
+        apply(
+            "3.20.0",
+            &Version::from_tag_name("3.20.6"),
+            false,
+            &Config::default(),
+            &storage,
+        )
+        .unwrap();
+        let current = cache_dir.join("current").read_link().unwrap();
+        let system_kept = cache_dir.join("system/3.20.0").exists();
+        cache::delete(&cache_dir).ok();
+
+        assert_eq!(current, cache_dir.join("versions/3.20.6"));
+        assert!(system_kept);
+    }
+}
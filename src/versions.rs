@@ -1,18 +1,106 @@
 use crate::constants::RELEASES_FILE_NAME;
-use crate::storage::Storage;
-use crate::{cache, package, platform};
+use crate::error::CmvmError;
+use crate::storage::{Storage, Store};
+use crate::{aliases, cache, package, platform};
 use anyhow::{bail, Result};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use std::path::{Path, PathBuf};
 
 #[derive(Serialize, Deserialize, Debug, Clone, Eq, Ord, PartialEq, PartialOrd)]
 pub struct Asset {
     pub name: String,
     pub content_type: String,
     pub browser_download_url: String,
+    #[serde(default)]
+    pub size: u64,
+    #[serde(default)]
+    pub updated_at: Option<String>,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone, Eq, Ord, PartialEq, PartialOrd)]
+/// A version found in `versions/` or in the system store, as reported by
+/// `cmvm list`.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct InstalledVersion {
+    pub version: String,
+    pub active: bool,
+    pub path: PathBuf,
+    pub aliases: Vec<String>,
+    pub store: Store,
+}
+
+/// Narrows `cmvm list-remote` down. The default keeps stable releases of a
+/// supported major version that have an asset for this platform.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RemoteFilter {
+    /// Also show prereleases, old major versions and releases without an
+    /// asset for this platform.
+    pub all: bool,
+    pub prerelease: bool,
+    pub major: Option<i32>,
+    pub minor: Option<i32>,
+    pub latest_per_minor: bool,
+    /// `YYYY-MM-DD`; releases published before that day are hidden.
+    pub since: Option<String>,
+    /// `Some(true)` keeps installed versions only, `Some(false)` the others.
+    pub installed: Option<bool>,
+}
+
+impl RemoteFilter {
+    pub fn matches(&self, version: &Version, installed: bool) -> bool {
+        self.major.is_none_or(|major| version.major == Some(major))
+            && self.minor.is_none_or(|minor| version.minor == Some(minor))
+            && self.installed.is_none_or(|wanted| wanted == installed)
+            && self.since.as_ref().is_none_or(|since| {
+                // RFC 3339 timestamps compare correctly as strings
+                version
+                    .published_at
+                    .as_ref()
+                    .is_some_and(|published_at| published_at.as_str() >= since.as_str())
+            })
+    }
+}
+
+/// Keeps the last version of each minor series from `versions`, which must
+/// be sorted.
+pub fn latest_per_minor(versions: Vec<Version>) -> Vec<Version> {
+    let mut latest: Vec<Version> = Vec::new();
+    for version in versions {
+        match latest.last_mut() {
+            Some(last) if (last.major, last.minor) == (version.major, version.minor) => {
+                *last = version
+            }
+            _ => latest.push(version),
+        }
+    }
+    latest
+}
+
+/// Whether `tag_name` names a release candidate, e.g. `3.29.0-rc2`. Other
+/// suffixes such as the `3.28.1-patched` of `install --as` are not one.
+fn is_rc_tag(tag_name: &str) -> bool {
+    tag_name
+        .split_once('-')
+        .and_then(|(_, suffix)| suffix.strip_prefix("rc"))
+        .is_some_and(|n| !n.is_empty() && n.chars().all(|c| c.is_ascii_digit()))
+}
+
+/// Parses a `--since` value, which must be a `YYYY-MM-DD` date.
+pub fn parse_date(value: &str) -> Result<String, String> {
+    let valid = value.len() == 10
+        && value.char_indices().all(|(i, c)| match i {
+            4 | 7 => c == '-',
+            _ => c.is_ascii_digit(),
+        });
+    match valid {
+        true => Ok(value.to_string()),
+        false => Err(format!("`{}` is not a YYYY-MM-DD date", value)),
+    }
+}
+
+/// Versions compare by release number and then tag name without its `v`;
+/// `v3.28.1` from the release index equals the installed `3.28.1`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Version {
     pub major: Option<i32>,
     pub minor: Option<i32>,
@@ -20,6 +108,13 @@ pub struct Version {
     pub prerelease: Option<bool>,
     pub tag_name: String,
     pub assets: Vec<Asset>,
+    #[serde(default)]
+    pub published_at: Option<String>,
+    #[serde(default)]
+    pub html_url: Option<String>,
+    /// Release notes, in markdown.
+    #[serde(default)]
+    pub body: Option<String>,
 }
 
 impl Version {
@@ -40,9 +135,31 @@ impl Version {
             prerelease: version.prerelease,
             tag_name: version.tag_name,
             assets: version.assets,
+            published_at: version.published_at,
+            html_url: version.html_url,
+            body: version.body,
         })
     }
 
+    pub fn from_tag_name(tag_name: &str) -> Version {
+        let mut version = Version {
+            major: None,
+            minor: None,
+            patch: None,
+            prerelease: Some(is_rc_tag(tag_name)),
+            tag_name: tag_name.to_string(),
+            assets: vec![],
+            published_at: None,
+            html_url: None,
+            body: None,
+        };
+        let version_vec = version.get_version_vec();
+        version.major = version_vec.first().copied();
+        version.minor = version_vec.get(1).copied();
+        version.patch = version_vec.get(2).copied();
+        version
+    }
+
     pub fn all_from_cache(storage: &impl Storage) -> Result<Vec<Version>> {
         let cache_dir = storage.get_cache_dir()?;
         let releases = cache::open_file(cache_dir.join(RELEASES_FILE_NAME))?;
@@ -57,18 +174,12 @@ impl Version {
 
     pub fn r#use(&mut self, storage: &impl Storage) -> Result<()> {
         let current_version_dir = storage.get_current_version_dir()?;
-        let versions_dir = storage.get_versions_dir()?;
-        let version_path = versions_dir.join(self.get_tag_name());
-
-        if !version_path.exists() {
-            bail!(
-                "[cmvm] Version {} is not installed. Use `cmvm install {}` first.",
-                self.get_tag_name(),
-                self.get_tag_name()
-            );
-        }
+        let Some((version_path, _)) = storage.find_version_dir(&self.get_tag_name())? else {
+            bail!(CmvmError::NotInstalled(self.get_tag_name()));
+        };
 
-        if current_version_dir.exists() {
+        // `symlink_metadata` also catches a dangling link, which `exists` misses
+        if current_version_dir.symlink_metadata().is_ok() {
             cache::delete(&current_version_dir)?;
         }
 
@@ -77,74 +188,182 @@ impl Version {
         Ok(())
     }
 
-    pub fn list(storage: &impl Storage) -> Result<String> {
-        let current_version_dir = storage.get_current_version_dir()?;
-        let versions_dir = storage.get_versions_dir()?;
-        let mut versions = cache::ls(&versions_dir)?;
+    /// Installed versions of both stores. A version in the user's
+    /// `versions/` hides the system store's one of the same name.
+    pub fn all_installed(storage: &impl Storage) -> Result<Vec<Version>> {
+        let mut versions: Vec<Version> = installed_dirs(storage)?
+            .into_iter()
+            .map(|(path, _)| Version::from_tag_name(&file_name(&path)))
+            .collect();
         versions.sort();
-        let mut mapped_versions: Vec<String> = Vec::new();
-        let current = current_version_dir.read_link().unwrap_or_default();
+        Ok(versions)
+    }
 
-        for version in versions {
-            if version.is_dir() {
-                let mut checked: &str = " ";
-                if version == current {
-                    checked = "*";
-                }
+    /// Installed versions of `store` only, e.g. the ones `prune` may remove.
+    pub fn all_installed_in(store: Store, storage: &impl Storage) -> Result<Vec<Version>> {
+        let mut versions: Vec<Version> = installed_dirs(storage)?
+            .into_iter()
+            .filter(|(_, s)| *s == store)
+            .map(|(path, _)| Version::from_tag_name(&file_name(&path)))
+            .collect();
+        versions.sort();
+        Ok(versions)
+    }
 
-                if let Some(file_name) = version.file_name() {
-                    let version_name = file_name.to_string_lossy();
-                    mapped_versions.push(format!("[cmvm] {} {}", checked, version_name));
-                }
-            }
+    pub fn list(storage: &impl Storage) -> Result<Vec<InstalledVersion>> {
+        let current_version_dir = storage.get_current_version_dir()?;
+        let mut versions = installed_dirs(storage)?;
+        versions.sort_by_key(|(path, _)| file_name(path));
+        let mut installed_versions: Vec<InstalledVersion> = Vec::new();
+        let current = current_version_dir.read_link().unwrap_or_default();
+
+        for (version, store) in versions {
+            let version_name = file_name(&version);
+            installed_versions.push(InstalledVersion {
+                aliases: aliases::for_version(&version_name, storage)?,
+                active: version == current,
+                version: version_name,
+                path: version,
+                store,
+            });
         }
-        Ok(mapped_versions.join("\n"))
+        Ok(installed_versions)
+    }
+
+    pub fn list_remote(storage: &impl Storage) -> Result<Vec<Version>> {
+        Version::list_remote_filtered(&RemoteFilter::default(), storage)
     }
 
-    pub fn list_remote(storage: &impl Storage) -> Result<String> {
+    pub fn list_remote_filtered(
+        filter: &RemoteFilter,
+        storage: &impl Storage,
+    ) -> Result<Vec<Version>> {
+        let supported_definition = platform::supported_definition();
         let mut versions: Vec<Version> = Vec::new();
 
         for version in Version::all_from_cache(storage)? {
-            // skip release candidate versions
-            if version.is_rc() {
-                continue;
-            }
+            if !filter.all {
+                // skip release candidate versions
+                if version.is_rc() && !filter.prerelease {
+                    continue;
+                }
+
+                // skip releases that doesn't match the required major version
+                if version.major.unwrap() < supported_definition.major_version_required {
+                    continue;
+                }
 
-            let supported_definition = platform::supported_definition();
+                let assets: Vec<&Asset> = package::filter_platform_assets(&version);
 
-            // skip releases that doesn't match the required major version
-            if version.major.unwrap() < supported_definition.major_version_required {
-                continue;
+                if assets.is_empty() {
+                    continue;
+                }
             }
 
-            let assets: Vec<&Asset> = package::filter_platform_assets(&version);
-
-            if !assets.is_empty() {
+            let installed = storage.find_version_dir(&version.get_tag_name())?.is_some();
+            if filter.matches(&version, installed) {
                 versions.push(version);
             }
         }
 
         versions.sort();
 
-        let version_tags: Vec<String> = versions
-            .into_iter()
-            .map(|v| format!("[cmvm] {}", v.get_tag_name()))
-            .collect();
+        if filter.latest_per_minor {
+            versions = latest_per_minor(versions);
+        }
 
-        Ok(version_tags.join("\n"))
+        Ok(versions)
     }
 
+    /// `3.29.0-rc2` becomes `[3, 29, 0, 2]`.
     fn get_version_vec(&self) -> Vec<i32> {
         self.get_tag_name()
             .replace("-", ".")
             .split('.')
-            .map(|s| s.parse::<i32>().unwrap_or(0))
+            .map(|s| {
+                s.trim_start_matches(|c: char| !c.is_ascii_digit())
+                    .parse::<i32>()
+                    .unwrap_or(0)
+            })
             .collect()
     }
 
-    fn is_rc(&self) -> bool {
+    pub fn is_rc(&self) -> bool {
         self.prerelease.unwrap_or(false)
     }
+
+    /// The `N` of a `-rcN` prerelease.
+    pub fn get_prerelease_number(&self) -> Option<i32> {
+        match self.is_rc() {
+            true => self.get_version_vec().get(3).copied(),
+            false => None,
+        }
+    }
+
+    /// Prereleases sort before the final release of the same version and
+    /// among themselves by their rc number.
+    fn get_ordering_key(&self) -> (i32, i32, i32, bool, i32) {
+        (
+            self.major.unwrap_or(0),
+            self.minor.unwrap_or(0),
+            self.patch.unwrap_or(0),
+            !self.is_rc(),
+            self.get_prerelease_number().unwrap_or(0),
+        )
+    }
+}
+
+impl Ord for Version {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.get_ordering_key()
+            .cmp(&other.get_ordering_key())
+            .then_with(|| self.get_tag_name().cmp(&other.get_tag_name()))
+    }
+}
+
+impl PartialEq for Version {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == std::cmp::Ordering::Equal
+    }
+}
+
+impl Eq for Version {}
+
+impl PartialOrd for Version {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Version directories of the user's `versions/` followed by the system
+/// store entries they do not hide. A missing system store is empty.
+fn installed_dirs(storage: &impl Storage) -> Result<Vec<(PathBuf, Store)>> {
+    let mut dirs: Vec<(PathBuf, Store)> = cache::ls(&storage.get_versions_dir()?)?
+        .into_iter()
+        .filter(|path| path.is_dir())
+        .map(|path| (path, Store::User))
+        .collect();
+
+    if let Some(system_dir) = storage.get_system_versions_dir()? {
+        if system_dir.is_dir() && system_dir != storage.get_versions_dir()? {
+            for path in cache::ls(&system_dir)? {
+                let hidden = dirs
+                    .iter()
+                    .any(|(dir, _)| dir.file_name() == path.file_name());
+                if path.is_dir() && !hidden {
+                    dirs.push((path, Store::System));
+                }
+            }
+        }
+    }
+    Ok(dirs)
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .to_string()
 }
 
 #[cfg(test)]
@@ -152,6 +371,7 @@ mod test {
     use std::vec;
 
     use super::*;
+    use crate::storage::test_support::MockStorage;
     use pretty_assertions::assert_eq;
     use serde_json::json;
     #[test]
@@ -163,6 +383,9 @@ mod test {
             tag_name: "v1.10.0".to_string(),
             assets: vec![],
             prerelease: Some(false),
+            published_at: None,
+            html_url: None,
+            body: None,
         };
 
         assert_eq!(version.get_tag_name(), "1.10.0");
@@ -177,6 +400,9 @@ mod test {
             prerelease: Some(false),
             tag_name: "v3.20.10".to_string(),
             assets: vec![],
+            published_at: None,
+            html_url: None,
+            body: None,
         };
 
         assert_eq!(version.major.unwrap(), 3);
@@ -209,6 +435,43 @@ mod test {
         assert_eq!(assets.unwrap().content_type, "application/gzip");
     }
 
+    #[test]
+    fn test_raw_version_keeps_release_metadata() {
+        let raw_version = json!({
+            "tag_name": "v3.28.1",
+            "published_at": "2023-12-21T16:28:07Z",
+            "html_url": "https://github.com/Kitware/CMake/releases/tag/v3.28.1",
+            "body": "CMake 3.28.1 is a bug-fix release.",
+            "assets": [{
+                "name": "cmake-3.28.1-linux-x86_64.tar.gz",
+                "browser_download_url": "http://fake_browser_download_url",
+                "content_type": "application/gzip",
+                "size": 52428800,
+                "updated_at": "2023-12-21T16:30:00Z"
+            }]
+        });
+
+        let version = Version::from_raw_value(raw_version).unwrap();
+
+        assert_eq!(
+            version.published_at.as_deref(),
+            Some("2023-12-21T16:28:07Z")
+        );
+        assert_eq!(
+            version.html_url.as_deref(),
+            Some("https://github.com/Kitware/CMake/releases/tag/v3.28.1")
+        );
+        assert_eq!(
+            version.body.as_deref(),
+            Some("CMake 3.28.1 is a bug-fix release.")
+        );
+        assert_eq!(version.assets[0].size, 52428800);
+        assert_eq!(
+            version.assets[0].updated_at.as_deref(),
+            Some("2023-12-21T16:30:00Z")
+        );
+    }
+
     #[test]
     fn test_raw_version_rc_converted_to_version_struct() {
         let raw_asset = json!({
@@ -233,25 +496,140 @@ mod test {
         assert_eq!(version.is_rc(), true);
     }
 
-    #[cfg(target_os = "linux")]
-    struct MockStorage {
-        cache_dir: std::path::PathBuf,
+    #[test]
+    fn test_prerelease_number_is_parsed() {
+        assert_eq!(
+            Version::from_tag_name("3.29.0-rc2").get_prerelease_number(),
+            Some(2)
+        );
+        assert_eq!(
+            Version::from_tag_name("3.29.0").get_prerelease_number(),
+            None
+        );
+    }
+
+    #[test]
+    fn test_only_rc_tags_are_prereleases() {
+        assert_eq!(Version::from_tag_name("3.29.0-rc2").is_rc(), true);
+        assert_eq!(Version::from_tag_name("v3.29.0-rc10").is_rc(), true);
+        assert_eq!(Version::from_tag_name("3.28.1-patched").is_rc(), false);
+        assert_eq!(Version::from_tag_name("my-build").is_rc(), false);
+        assert_eq!(Version::from_tag_name("3.29.0-rc").is_rc(), false);
+    }
+
+    #[test]
+    fn test_prereleases_sort_before_final_release() {
+        let mut versions: Vec<Version> = ["3.29.0", "3.29.0-rc10", "3.28.3", "3.29.0-rc2"]
+            .iter()
+            .map(|t| Version::from_tag_name(t))
+            .collect();
+        versions.sort();
+
+        let tags: Vec<String> = versions.iter().map(Version::get_tag_name).collect();
+        assert_eq!(tags, vec!["3.28.3", "3.29.0-rc2", "3.29.0-rc10", "3.29.0"]);
+    }
+
+    #[test]
+    fn test_equality_agrees_with_ordering() {
+        let cached = Version::from_raw_value(json!({
+            "tag_name": "v3.28.1",
+            "published_at": "2023-12-21T16:28:07Z",
+            "assets": []
+        }))
+        .unwrap();
+        let installed = Version::from_tag_name("3.28.1");
+        let patched = Version::from_tag_name("3.28.1-patched");
+
+        assert_eq!(cached.cmp(&installed), std::cmp::Ordering::Equal);
+        assert_eq!(cached, installed);
+        assert_ne!(installed.cmp(&patched), std::cmp::Ordering::Equal);
+        assert_ne!(installed, patched);
+    }
+
+    fn dated(tag_name: &str, published_at: &str) -> Version {
+        let mut version = Version::from_tag_name(tag_name);
+        version.published_at = Some(published_at.to_string());
+        version
     }
 
+    #[test]
+    fn test_remote_filter_matches_major_minor_and_since() {
+        let filter = RemoteFilter {
+            major: Some(3),
+            minor: Some(28),
+            since: Some("2024-01-01".to_string()),
+            ..RemoteFilter::default()
+        };
+
+        assert!(filter.matches(&dated("3.28.3", "2024-02-05T12:00:00Z"), false));
+        assert!(!filter.matches(&dated("3.28.1", "2023-12-21T16:28:07Z"), false));
+        assert!(!filter.matches(&dated("3.29.0", "2024-03-01T12:00:00Z"), false));
+        assert!(!filter.matches(&Version::from_tag_name("3.28.4"), false));
+    }
+
+    #[test]
+    fn test_remote_filter_matches_installed_state() {
+        let installed_only = RemoteFilter {
+            installed: Some(true),
+            ..RemoteFilter::default()
+        };
+        let version = Version::from_tag_name("3.28.1");
+
+        assert!(installed_only.matches(&version, true));
+        assert!(!installed_only.matches(&version, false));
+        assert!(RemoteFilter::default().matches(&version, false));
+    }
+
+    #[test]
+    fn test_latest_per_minor_keeps_newest_of_each_series() {
+        let versions = ["3.27.0", "3.27.9", "3.28.0", "3.28.3", "4.0.0"]
+            .iter()
+            .map(|t| Version::from_tag_name(t))
+            .collect();
+
+        let latest: Vec<String> = latest_per_minor(versions)
+            .iter()
+            .map(Version::get_tag_name)
+            .collect();
+
+        assert_eq!(latest, vec!["3.27.9", "3.28.3", "4.0.0"]);
+    }
+
+    #[test]
+    fn test_parse_date() {
+        assert_eq!(parse_date("2024-01-31"), Ok("2024-01-31".to_string()));
+        assert!(parse_date("2024-1-31").is_err());
+        assert!(parse_date("yesterday").is_err());
+    }
+
+    #[test]
     #[cfg(target_os = "linux")]
-    impl Storage for MockStorage {
-        fn get_cache_dir(&self) -> anyhow::Result<std::path::PathBuf> {
-            Ok(self.cache_dir.clone())
-        }
-        fn get_data_dir(&self) -> anyhow::Result<std::path::PathBuf> {
-            Ok(self.cache_dir.clone())
-        }
-        fn get_current_version_dir(&self) -> anyhow::Result<std::path::PathBuf> {
-            Ok(self.cache_dir.join("current"))
-        }
-        fn get_versions_dir(&self) -> anyhow::Result<std::path::PathBuf> {
-            Ok(self.cache_dir.join("versions"))
-        }
+    fn test_list_remote_filtered_includes_prereleases_on_request() {
+        let cache_dir = std::env::temp_dir().join("cmvm_test_list_remote_prerelease");
+        let _ = std::fs::remove_dir_all(&cache_dir);
+        let raw = json!([
+            {
+                "assets": [{"browser_download_url": "https://fake", "content_type": "application/gzip", "name": "cmake-3.25.0-linux-x86_64.tar.gz"}],
+                "tag_name": "v3.25.0",
+                "prerelease": false
+            },
+            {
+                "assets": [{"browser_download_url": "https://fake", "content_type": "application/gzip", "name": "cmake-3.26.0-rc1-linux-x86_64.tar.gz"}],
+                "tag_name": "v3.26.0-rc1",
+                "prerelease": true
+            }
+        ]);
+        write_releases(&cache_dir, &raw);
+        let storage = MockStorage::new(&cache_dir);
+        let filter = RemoteFilter {
+            prerelease: true,
+            ..RemoteFilter::default()
+        };
+
+        let result = Version::list_remote_filtered(&filter, &storage).unwrap();
+        cache::delete(&cache_dir).ok();
+
+        assert_eq!(tag_names(result), vec!["3.25.0", "3.26.0-rc1"]);
     }
 
     #[cfg(target_os = "linux")]
@@ -263,6 +641,11 @@ mod test {
         f.write_all(raw.to_string().as_bytes()).unwrap();
     }
 
+    #[cfg(target_os = "linux")]
+    fn tag_names(versions: Vec<Version>) -> Vec<String> {
+        versions.iter().map(Version::get_tag_name).collect()
+    }
+
     #[test]
     #[cfg(target_os = "linux")]
     fn test_list_remote_excludes_rc_versions() {
@@ -281,13 +664,11 @@ mod test {
             }
         ]);
         write_releases(&cache_dir, &raw);
-        let storage = MockStorage {
-            cache_dir: cache_dir.clone(),
-        };
-        let result = Version::list_remote(&storage).unwrap();
+        let storage = MockStorage::new(&cache_dir);
+        let result = tag_names(Version::list_remote(&storage).unwrap());
         let _ = std::fs::remove_dir_all(&cache_dir);
-        assert!(result.contains("3.25.0"));
-        assert!(!result.contains("3.25.0-rc1"));
+        assert!(result.contains(&"3.25.0".to_string()));
+        assert!(!result.contains(&"3.25.0-rc1".to_string()));
     }
 
     #[test]
@@ -308,13 +689,11 @@ mod test {
             }
         ]);
         write_releases(&cache_dir, &raw);
-        let storage = MockStorage {
-            cache_dir: cache_dir.clone(),
-        };
-        let result = Version::list_remote(&storage).unwrap();
+        let storage = MockStorage::new(&cache_dir);
+        let result = tag_names(Version::list_remote(&storage).unwrap());
         let _ = std::fs::remove_dir_all(&cache_dir);
-        assert!(result.contains("3.20.0"));
-        assert!(!result.contains("2.8.0"));
+        assert!(result.contains(&"3.20.0".to_string()));
+        assert!(!result.contains(&"2.8.0".to_string()));
     }
 
     #[test]
@@ -340,16 +719,10 @@ mod test {
             }
         ]);
         write_releases(&cache_dir, &raw);
-        let storage = MockStorage {
-            cache_dir: cache_dir.clone(),
-        };
-        let result = Version::list_remote(&storage).unwrap();
+        let storage = MockStorage::new(&cache_dir);
+        let result = tag_names(Version::list_remote(&storage).unwrap());
         let _ = std::fs::remove_dir_all(&cache_dir);
-        let lines: Vec<&str> = result.lines().collect();
-        assert_eq!(lines.len(), 3);
-        assert!(lines[0].contains("3.20.0"));
-        assert!(lines[1].contains("3.21.0"));
-        assert!(lines[2].contains("3.22.0"));
+        assert_eq!(result, vec!["3.20.0", "3.21.0", "3.22.0"]);
     }
 
     #[test]
@@ -362,15 +735,56 @@ mod test {
         std::fs::create_dir_all(versions_dir.join("3.22.0")).unwrap();
         std::fs::create_dir_all(versions_dir.join("3.20.0")).unwrap();
         std::fs::create_dir_all(versions_dir.join("3.21.0")).unwrap();
-        let storage = MockStorage {
-            cache_dir: cache_dir.clone(),
-        };
+        let storage = MockStorage::new(&cache_dir);
+        let result = Version::list(&storage).unwrap();
+        let _ = std::fs::remove_dir_all(&cache_dir);
+        let names: Vec<&str> = result.iter().map(|v| v.version.as_str()).collect();
+        assert_eq!(names, vec!["3.20.0", "3.21.0", "3.22.0"]);
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_list_reports_aliases_and_active() {
+        let cache_dir = std::env::temp_dir().join("cmvm_test_list_aliases");
+        let _ = std::fs::remove_dir_all(&cache_dir);
+        let versions_dir = cache_dir.join("versions");
+        std::fs::create_dir_all(versions_dir.join("3.20.0")).unwrap();
+        std::fs::create_dir_all(versions_dir.join("3.28.1")).unwrap();
+        let storage = MockStorage::new(&cache_dir);
+        aliases::set("legacy", "3.20.0", &storage).unwrap();
+        aliases::set("ci", "3.20.0", &storage).unwrap();
+        std::os::unix::fs::symlink(versions_dir.join("3.28.1"), cache_dir.join("current")).unwrap();
+
         let result = Version::list(&storage).unwrap();
         let _ = std::fs::remove_dir_all(&cache_dir);
-        let lines: Vec<&str> = result.lines().collect();
-        assert_eq!(lines.len(), 3);
-        assert!(lines[0].contains("3.20.0"));
-        assert!(lines[1].contains("3.21.0"));
-        assert!(lines[2].contains("3.22.0"));
+        assert_eq!(result[0].aliases, vec!["ci", "legacy"]);
+        assert!(!result[0].active);
+        assert_eq!(result[1].aliases, Vec::<String>::new());
+        assert!(result[1].active);
+        assert_eq!(result[1].path, versions_dir.join("3.28.1"));
+    }
+
+    #[test]
+    fn test_list_includes_system_store() {
+        let cache_dir = std::env::temp_dir().join("cmvm_test_list_system_store");
+        let _ = std::fs::remove_dir_all(&cache_dir);
+        for dir in ["versions/3.28.1", "system/3.20.0", "system/3.28.1"] {
+            std::fs::create_dir_all(cache_dir.join(dir)).unwrap();
+        }
+        let storage = MockStorage::new(&cache_dir);
+        std::os::unix::fs::symlink(cache_dir.join("system/3.20.0"), cache_dir.join("current"))
+            .unwrap();
+
+        let result = Version::list(&storage).unwrap();
+        let user_only = Version::all_installed_in(Store::User, &storage).unwrap();
+        let _ = std::fs::remove_dir_all(&cache_dir);
+
+        assert_eq!(result.len(), 2);
+        assert_eq!(result[0].version, "3.20.0");
+        assert_eq!(result[0].store, Store::System);
+        assert!(result[0].active);
+        assert_eq!(result[1].path, cache_dir.join("versions/3.28.1"));
+        assert_eq!(result[1].store, Store::User);
+        assert_eq!(user_only, vec![Version::from_tag_name("3.28.1")]);
     }
 }